@@ -0,0 +1,74 @@
+//! Named sub-agent definitions, loaded from `~/.enchant/agents/*.md` and
+//! `.enchant/agents/*.md`. Project definitions override user ones by name.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{
+    agent::config::user_dir,
+    util::front_matter::{parse_front_matter, read_markdown_dir, string_list},
+};
+
+/// A named specialist the main agent can delegate to with the `Task` tool.
+#[derive(Clone, Debug)]
+pub struct AgentDefinition {
+    pub name: String,
+    pub description: String,
+    /// Tools the agent may use. Defaults to the `subagent` config.
+    pub tools: Option<Vec<String>>,
+    /// Model the agent runs on. Defaults to the `subagent` config.
+    pub model: Option<String>,
+    /// System prompt template, rendered like the base prompt.
+    pub prompt: String,
+}
+
+#[derive(Default, Deserialize)]
+struct AgentFrontMatter {
+    name: Option<String>,
+    description: Option<String>,
+    #[serde(default, deserialize_with = "string_list")]
+    tools: Option<Vec<String>>,
+    model: Option<String>,
+}
+
+/// Loads all agent definitions. Files that fail to parse are reported as
+/// warnings instead of failing the whole load.
+pub async fn load_agent_definitions(
+    working_directory: impl AsRef<Path>,
+) -> (Vec<AgentDefinition>, Vec<String>) {
+    let mut agents: Vec<AgentDefinition> = vec![];
+    let mut warnings = vec![];
+
+    let dirs = [
+        user_dir().join("agents"),
+        working_directory.as_ref().join(".enchant").join("agents"),
+    ];
+    for dir in dirs {
+        for (path, content) in read_markdown_dir(&dir).await {
+            let (meta, prompt) = match parse_front_matter::<AgentFrontMatter>(&content) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    warnings.push(format!("Invalid agent definition {}: {e:?}", path.display()));
+                    continue;
+                }
+            };
+            let name = meta.name.unwrap_or_else(|| {
+                path.file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            });
+
+            agents.retain(|a| a.name != name);
+            agents.push(AgentDefinition {
+                name,
+                description: meta.description.unwrap_or_default(),
+                tools: meta.tools,
+                model: meta.model,
+                prompt,
+            });
+        }
+    }
+
+    (agents, warnings)
+}
@@ -0,0 +1,153 @@
+//! Limits that halt the agent loop before it runs away.
+
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::usage::{format_cost, format_tokens};
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct BudgetConfig {
+    /// Model calls per prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_turns: Option<usize>,
+    /// Tool calls per session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tool_calls: Option<usize>,
+    /// Tokens per session, cached tokens included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    /// Dollars per session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost: Option<f64>,
+}
+
+impl BudgetConfig {
+    pub fn merge(self, overlay: Self) -> Self {
+        Self {
+            max_turns: overlay.max_turns.or(self.max_turns),
+            max_tool_calls: overlay.max_tool_calls.or(self.max_tool_calls),
+            max_tokens: overlay.max_tokens.or(self.max_tokens),
+            max_cost: overlay.max_cost.or(self.max_cost),
+        }
+    }
+
+    /// What is left of the session limits after `usage`, for a sub-agent.
+    /// `max_turns` is kept, as the sub-agent's turns are counted on their own.
+    pub fn remaining(&self, usage: &BudgetUsage) -> Self {
+        Self {
+            max_turns: self.max_turns,
+            max_tool_calls: self
+                .max_tool_calls
+                .map(|max| max.saturating_sub(usage.tool_calls)),
+            max_tokens: self.max_tokens.map(|max| max.saturating_sub(usage.tokens)),
+            max_cost: self.max_cost.map(|max| (max - usage.cost).max(0.0)),
+        }
+    }
+}
+
+/// A limit that has been reached, with its value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BudgetLimit {
+    Turns(usize),
+    ToolCalls(usize),
+    Tokens(u64),
+    Cost(f64),
+}
+
+impl fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Turns(max) => write!(f, "{max} model turns for this prompt"),
+            Self::ToolCalls(max) => write!(f, "{max} tool calls"),
+            Self::Tokens(max) => write!(f, "{} tokens", format_tokens(*max)),
+            Self::Cost(max) => write!(f, "{} spent", format_cost(*max)),
+        }
+    }
+}
+
+/// What has been used against the budget.
+#[derive(Clone, Debug, Default)]
+pub struct BudgetUsage {
+    /// Model calls since the last prompt.
+    pub turns: usize,
+    pub tool_calls: usize,
+    pub tokens: u64,
+    pub cost: f64,
+}
+
+impl BudgetUsage {
+    /// The first limit of `budget` that has been reached, if any.
+    pub fn exceeded(&self, budget: &BudgetConfig) -> Option<BudgetLimit> {
+        if let Some(max) = budget.max_turns
+            && self.turns >= max
+        {
+            return Some(BudgetLimit::Turns(max));
+        }
+        if let Some(max) = budget.max_tool_calls
+            && self.tool_calls >= max
+        {
+            return Some(BudgetLimit::ToolCalls(max));
+        }
+        if let Some(max) = budget.max_tokens
+            && self.tokens >= max
+        {
+            return Some(BudgetLimit::Tokens(max));
+        }
+        if let Some(max) = budget.max_cost
+            && self.cost >= max
+        {
+            return Some(BudgetLimit::Cost(max));
+        }
+        None
+    }
+
+    /// Raises `limit` in `budget` so the agent can run as long again.
+    pub fn extend(&mut self, budget: &mut BudgetConfig, limit: &BudgetLimit) {
+        match limit {
+            BudgetLimit::Turns(_) => self.turns = 0,
+            BudgetLimit::ToolCalls(max) => budget.max_tool_calls = Some(self.tool_calls + max),
+            BudgetLimit::Tokens(max) => budget.max_tokens = Some(self.tokens + max),
+            BudgetLimit::Cost(max) => budget.max_cost = Some(self.cost + max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let mut budget = BudgetConfig {
+            max_turns: Some(3),
+            max_cost: Some(1.0),
+            ..Default::default()
+        };
+        let mut usage = BudgetUsage {
+            turns: 2,
+            cost: 1.25,
+            ..Default::default()
+        };
+        assert_eq!(usage.exceeded(&budget), Some(BudgetLimit::Cost(1.0)));
+
+        usage.extend(&mut budget, &BudgetLimit::Cost(1.0));
+        assert_eq!(budget.max_cost, Some(2.25));
+        assert_eq!(usage.exceeded(&budget), None);
+
+        usage.turns = 3;
+        assert_eq!(usage.exceeded(&budget), Some(BudgetLimit::Turns(3)));
+        usage.extend(&mut budget, &BudgetLimit::Turns(3));
+        assert_eq!(usage.exceeded(&budget), None);
+
+        usage.cost = 2.0;
+        let remaining = budget.remaining(&usage);
+        assert_eq!(remaining.max_turns, Some(3));
+        assert_eq!(remaining.max_tool_calls, None);
+        assert_eq!(remaining.max_cost, Some(0.25));
+        usage.cost = 3.0;
+        assert_eq!(budget.remaining(&usage).max_cost, Some(0.0));
+    }
+}
@@ -0,0 +1,72 @@
+//! Prompt caching breakpoints for Anthropic models.
+//!
+//! Anthropic caches the request prefix up to each message marked with
+//! `cache_control`. The prefix is the tool list, then the system prompt, then
+//! the history, so a breakpoint on the system prompt covers the tools as well.
+
+use genai::{
+    adapter::AdapterKind,
+    chat::{CacheControl, ChatMessage, ChatRole},
+};
+
+/// Marks the cache breakpoints on `messages` for a request to `model`:
+/// one after the system prompt, and a rolling one on the last message.
+pub fn with_cache_breakpoints(mut messages: Vec<ChatMessage>, model: &str) -> Vec<ChatMessage> {
+    if AdapterKind::from_model(model).ok() != Some(AdapterKind::Anthropic) || messages.is_empty() {
+        return messages;
+    }
+
+    let system_count = messages
+        .iter()
+        .take_while(|m| matches!(m.role, ChatRole::System))
+        .count();
+    // Anthropic only caches system messages after the first, so with a single
+    // one the breakpoint goes on the first message of the conversation instead.
+    let system_breakpoint = if system_count > 1 {
+        Some(system_count - 1)
+    } else {
+        (messages.len() > system_count).then_some(system_count)
+    };
+
+    let last = messages.len() - 1;
+    for index in system_breakpoint.into_iter().chain([last]) {
+        let message = messages[index].clone();
+        messages[index] = message.with_options(CacheControl::Ephemeral);
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(messages: &[ChatMessage]) -> Vec<bool> {
+        messages
+            .iter()
+            .map(|m| {
+                m.options
+                    .as_ref()
+                    .is_some_and(|o| o.cache_control.is_some())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let messages = vec![
+            ChatMessage::system("prompt"),
+            ChatMessage::system("ENCHANT.md"),
+            ChatMessage::user("hi"),
+            ChatMessage::assistant("hello"),
+            ChatMessage::user("bye"),
+        ];
+        let marked = with_cache_breakpoints(messages.clone(), "claude-haiku-4-5");
+        assert_eq!(cached(&marked), [false, true, false, false, true]);
+
+        let marked = with_cache_breakpoints(messages[1..].to_vec(), "claude-haiku-4-5");
+        assert_eq!(cached(&marked), [false, true, false, true]);
+
+        let marked = with_cache_breakpoints(messages, "gpt-5.2");
+        assert!(cached(&marked).iter().all(|c| !c));
+    }
+}
@@ -4,38 +4,119 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use schemars::{JsonSchema, schema_for};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
 use tokio::fs;
 
-use crate::error::Error;
+use crate::{
+    agent::{
+        budget::BudgetConfig,
+        models::model_info::{ModelInfoMap, merge_models},
+    },
+    error::Error,
+    util::{
+        env::{Interpolation, References, interpolate_json},
+        json_schema::{validate, validate_secrets},
+    },
+};
 
-// ~/.enchant/config.json
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+/// Settings of `enchant.json`. The config of a session is merged from
+/// several layers (see `ConfigState::layers`); each field documents how a
+/// layer is merged over the ones below it.
+#[derive(Default, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Config {
+    /// Replaced by later layers.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_model: Option<String>,
 
-    /// Optional MCP stdio servers to load tools from.
+    /// Models tried in order when the current one is overloaded or unavailable.
+    /// Replaced as a whole by later layers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_models: Vec<String>,
+
+    /// MCP servers to load tools from. A server of a later layer replaces
+    /// the one with the same name.
     #[serde(default)]
     pub mcp_servers: Vec<crate::agent::mcp::McpServerConfig>,
 
+    /// Allowlists of later layers are added to the earlier ones.
     #[serde(default)]
     pub permissions: Permissions,
+
+    /// Settings for sub-agents spawned by the `Task` tool.
+    #[serde(default)]
+    pub subagent: SubagentConfig,
+
+    /// Limits that halt the agent loop.
+    #[serde(default)]
+    pub budget: BudgetConfig,
+
+    /// Custom models, merged over the built-in ones and `~/.enchant/models.json`,
+    /// field by field.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub models: ModelInfoMap,
 }
 
 impl Config {
+    /// `overlay` merged over `self`, by the strategy of each field.
+    /// `subagent` and `budget` are merged field by field.
     pub fn merge(self, overlay: Self) -> Self {
-        let mut mcp_servers = overlay.mcp_servers;
-        mcp_servers.extend(self.mcp_servers);
-        return Self {
+        let mut mcp_servers = self.mcp_servers;
+        for server in overlay.mcp_servers {
+            match mcp_servers.iter_mut().find(|s| s.name == server.name) {
+                Some(existing) => *existing = server,
+                None => mcp_servers.push(server),
+            }
+        }
+        Self {
             default_model: overlay.default_model.or(self.default_model),
+            fallback_models: if overlay.fallback_models.is_empty() {
+                self.fallback_models
+            } else {
+                overlay.fallback_models
+            },
             mcp_servers,
             permissions: overlay.permissions.merge(self.permissions),
-        };
+            subagent: self.subagent.merge(overlay.subagent),
+            budget: self.budget.merge(overlay.budget),
+            models: merge_models(self.models, overlay.models),
+        }
     }
 }
 
-#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct SubagentConfig {
+    /// Model used by sub-agents. Defaults to `default_model`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Tools available to sub-agents. Defaults to the read-only tools.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+}
+
+impl SubagentConfig {
+    pub fn merge(self, overlay: Self) -> Self {
+        Self {
+            model: overlay.model.or(self.model),
+            tools: overlay.tools.or(self.tools),
+        }
+    }
+
+    pub fn tools(&self) -> Vec<String> {
+        self.tools.clone().unwrap_or_else(|| {
+            ["Read", "Glob", "Grep", "LS"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect()
+        })
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Permissions {
     pub bash: BashConfig,
 }
@@ -44,13 +125,14 @@ impl Permissions {
     pub fn merge(mut self, overlay: Self) -> Self {
         let mut allowlist = overlay.bash.allow;
         allowlist.append(&mut self.bash.allow);
-        return Self {
+        Self {
             bash: BashConfig { allow: allowlist },
-        };
+        }
     }
 }
 
-#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct BashConfig {
     pub allow: Vec<String>,
 }
@@ -58,13 +140,58 @@ pub struct BashConfig {
 // ~/.enchant/api-keys.json
 pub type ProviderKeys = HashMap<String, ProviderKey>;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "provider")]
+#[schemars(deny_unknown_fields)]
 pub enum ProviderKey {
     #[serde(rename = "openai")]
     OpenAI { api_key: String },
     #[serde(rename = "anthropic")]
     Anthropic { api_key: String },
+    #[serde(rename = "gemini")]
+    Gemini { api_key: String },
+    #[serde(rename = "groq")]
+    Groq { api_key: String },
+    #[serde(rename = "xai")]
+    XAi { api_key: String },
+    #[serde(rename = "deepseek")]
+    DeepSeek { api_key: String },
+    #[serde(rename = "openrouter")]
+    OpenRouter { api_key: String },
+    /// A local Ollama server. `base_url` defaults to `http://localhost:11434/v1/`.
+    #[serde(rename = "ollama")]
+    Ollama {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        base_url: Option<String>,
+    },
+    /// Any server implementing the OpenAI chat completions API. Its models are
+    /// addressed as `<key name>::<model>`, e.g. `together::meta-llama/Llama-3.3-70B`.
+    #[serde(rename = "openai_compatible")]
+    OpenAICompatible {
+        base_url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key: Option<String>,
+    },
+    /// A secret for `${key:NAME}` references in `enchant.json`, not used by any provider.
+    #[serde(rename = "secret")]
+    Secret { api_key: String },
+}
+
+impl ProviderKey {
+    pub fn api_key(&self) -> Option<&str> {
+        match self {
+            Self::OpenAI { api_key }
+            | Self::Anthropic { api_key }
+            | Self::Gemini { api_key }
+            | Self::Groq { api_key }
+            | Self::XAi { api_key }
+            | Self::DeepSeek { api_key }
+            | Self::OpenRouter { api_key }
+            | Self::Secret { api_key } => Some(api_key),
+            Self::OpenAICompatible { api_key, .. } => api_key.as_deref(),
+            Self::Ollama { .. } => None,
+        }
+    }
 }
 
 pub async fn check_directory(path: impl AsRef<Path>) -> Result<PathBuf, Error> {
@@ -89,23 +216,497 @@ pub async fn check_file(
 pub async fn check_json<T: Serialize + DeserializeOwned + Default>(
     path: impl AsRef<Path>,
 ) -> Result<T, Error> {
+    let path = path.as_ref();
+    let content = check_file(path, || serde_json::to_string(&T::default()).unwrap()).await?;
+    serde_json::from_str(&content)
+        .map_err(|e| Error::SerdeError(format!("Invalid {}: {e}", path.display())))
+}
+
+/// Like `check_json`, but validated against the type's JSON Schema, and
+/// with the `${...}` references in its values handled by `interpolation`.
+pub async fn check_config_json<T: Serialize + DeserializeOwned + JsonSchema + Default>(
+    path: impl AsRef<Path>,
+    interpolation: &Interpolation<'_>,
+) -> Result<T, Error> {
+    let path = path.as_ref();
     let content = check_file(path, || serde_json::to_string(&T::default()).unwrap()).await?;
-    Ok(serde_json::from_str(&content)?)
+    parse_config(path, &content, interpolation)
+}
+
+/// Looks up the API key of an entry of `api-keys.json`, for `${key:NAME}`.
+fn key_lookup(keys: &ProviderKeys) -> impl Fn(&str) -> Option<String> + Sync + '_ {
+    |name| keys.get(name)?.api_key().map(str::to_string)
 }
 
+/// The JSON Schema of `enchant.json`, for editor autocompletion.
+pub fn config_schema() -> Value {
+    schema_for!(Config).to_value()
+}
+
+fn parse_config<T: DeserializeOwned + JsonSchema>(
+    path: &Path,
+    content: &str,
+    interpolation: &Interpolation,
+) -> Result<T, Error> {
+    let value = serde_json::from_str(content).map_err(|e| Error::ConfigError {
+        path: path.to_path_buf(),
+        problems: vec![e.to_string()],
+    })?;
+    config_from_value(path, value, interpolation)
+}
+
+fn config_from_value<T: DeserializeOwned + JsonSchema>(
+    path: &Path,
+    mut value: Value,
+    interpolation: &Interpolation,
+) -> Result<T, Error> {
+    let invalid = |problems: Vec<String>| Error::ConfigError {
+        path: path.to_path_buf(),
+        problems,
+    };
+    // `$schema` points editors at the schema; it isn't a setting.
+    if let Some(object) = value.as_object_mut() {
+        object.remove("$schema");
+    }
+
+    // Validated before interpolation, so that problems never show secrets.
+    // References therefore only work in string fields.
+    let schema = schema_for!(T).to_value();
+    let errors = if path.ends_with(API_KEYS_FILE) {
+        validate_secrets(&schema, &value)
+    } else {
+        validate(&schema, &value)
+    };
+    if !errors.is_empty() {
+        return Err(invalid(errors.iter().map(ToString::to_string).collect()));
+    }
+    interpolate_json(&mut value, interpolation).map_err(|e| invalid(vec![e.to_string()]))?;
+    serde_json::from_value(value).map_err(|e| invalid(vec![e.to_string()]))
+}
+
+/// The file of the user's API keys, in `user_dir`. Problems with it never show
+/// its strings.
+const API_KEYS_FILE: &str = "api-keys.json";
+
+/// The origin of settings given on the command line.
+pub const COMMAND_LINE: &str = "command line";
+
+/// Settings from one config file, or from the command line.
 #[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    /// Where the settings come from, e.g. `~/.enchant/enchant.json` or `COMMAND_LINE`.
+    pub origin: String,
+    pub config: Config,
+}
 
+#[derive(Debug, Clone)]
 pub struct ConfigState {
+    /// `~/.enchant/enchant.json`.
     pub base: Config,
+    /// `~/.enchant/models.json`.
+    pub models: ModelInfoMap,
     pub api_keys: ProviderKeys,
+    /// Settings from the command line, applied over every config file.
+    pub overrides: Config,
+    /// Problems of the user's config files, which were ignored because of them.
+    pub warnings: Vec<String>,
+    /// What is done with the `${...}` references of the config files.
+    pub references: References,
 }
 
-pub async fn load_config() -> Result<ConfigState, Error> {
-    let enchant_dir = check_directory(env::home_dir().unwrap().join(".enchant")).await?;
+impl ConfigState {
+    /// The layers of the config of a session in `working_directory`, each
+    /// merged over the ones before it: `~/.enchant/models.json`,
+    /// `~/.enchant/enchant.json`, the project's `enchant.json` and
+    /// `enchant.local.json`, and the command line.
+    ///
+    /// Project files that are missing are left out, and invalid ones are
+    /// returned as errors instead.
+    pub async fn layers(&self, working_directory: &Path) -> (Vec<ConfigLayer>, Vec<Error>) {
+        let mut layers = vec![
+            ConfigLayer {
+                origin: "~/.enchant/models.json".to_string(),
+                config: Config {
+                    models: self.models.clone(),
+                    ..Config::default()
+                },
+            },
+            ConfigLayer {
+                origin: "~/.enchant/enchant.json".to_string(),
+                config: self.base.clone(),
+            },
+        ];
+        let mut errors = vec![];
+        // Project files come with the repository, so they aren't trusted.
+        let interpolation = Interpolation {
+            keys: &key_lookup(&self.api_keys),
+            trusted: false,
+            references: self.references,
+        };
+        for name in ["enchant.json", "enchant.local.json"] {
+            let path = working_directory.join(name);
+            let Ok(content) = fs::read_to_string(&path).await else {
+                continue;
+            };
+            match parse_config(&path, &content, &interpolation) {
+                Ok(config) => layers.push(ConfigLayer {
+                    origin: name.to_string(),
+                    config,
+                }),
+                Err(err) => errors.push(err),
+            }
+        }
+        layers.push(ConfigLayer {
+            origin: COMMAND_LINE.to_string(),
+            config: self.overrides.clone(),
+        });
+        (layers, errors)
+    }
 
-    let base: Config = check_json(enchant_dir.clone().join("enchant.json")).await?; // ~/.enchant/.enchant.json
+    /// The config of a session in `working_directory`: all its layers
+    /// merged. Invalid project files are ignored and returned as errors.
+    pub async fn session_config(&self, working_directory: &Path) -> (Config, Vec<Error>) {
+        let (layers, errors) = self.layers(working_directory).await;
+        (merge_layers(&layers), errors)
+    }
+}
 
-    let api_keys: ProviderKeys = check_json(enchant_dir.clone().join("api-keys.json")).await?;
+fn merge_layers(layers: &[ConfigLayer]) -> Config {
+    layers
+        .iter()
+        .fold(Config::default(), |config, layer| config.merge(layer.config.clone()))
+}
 
-    Ok(ConfigState { base, api_keys })
+/// A setting of the effective config, such as `budget.max_cost` or
+/// `mcp_servers.github.command`, with the layers it comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub key: String,
+    pub value: Value,
+    pub origins: Vec<String>,
+}
+
+/// Settings whose values are concatenated across layers, rather than replaced.
+const CONCATENATED: &[&str] = &["permissions.bash.allow"];
+
+/// The settings of the merged `layers`, and where each comes from: the
+/// last layer that sets it, or every layer adding to it.
+pub fn effective_settings(layers: &[ConfigLayer]) -> Vec<Setting> {
+    let layer_settings: Vec<(&str, Vec<(String, Value)>)> = layers
+        .iter()
+        .map(|layer| (layer.origin.as_str(), flatten_config(&layer.config)))
+        .collect();
+    flatten_config(&merge_layers(layers))
+        .into_iter()
+        .map(|(key, value)| {
+            let mut origins: Vec<String> = layer_settings
+                .iter()
+                .filter(|(_, settings)| settings.iter().any(|(k, _)| *k == key))
+                .map(|(origin, _)| origin.to_string())
+                .collect();
+            if !CONCATENATED.contains(&key.as_str()) {
+                origins.drain(..origins.len().saturating_sub(1));
+            }
+            Setting {
+                key,
+                value,
+                origins,
+            }
+        })
+        .collect()
+}
+
+/// The settings of `config` by dotted key. MCP servers are keyed by name,
+/// and empty values are left out.
+fn flatten_config(config: &Config) -> Vec<(String, Value)> {
+    let mut value = serde_json::to_value(config).unwrap();
+    if let Some(servers) = value.get_mut("mcp_servers") {
+        let by_name = servers
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|server| {
+                let mut server = server.clone();
+                let name = server.as_object_mut()?.remove("name")?;
+                Some((name.as_str()?.to_string(), server))
+            });
+        *servers = Value::Object(by_name.collect());
+    }
+    let mut settings = vec![];
+    flatten_value(&value, String::new(), &mut settings);
+    settings
+}
+
+fn flatten_value(value: &Value, key: String, settings: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(object) => {
+            for (name, item) in object {
+                let key = if key.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{key}.{name}")
+                };
+                flatten_value(item, key, settings);
+            }
+        }
+        Value::Null => {}
+        Value::Array(items) if items.is_empty() => {}
+        Value::String(text) if text.is_empty() => {}
+        _ => settings.push((key, value.clone())),
+    }
+}
+
+/// Settings given with `--config key=value`. Keys are dotted paths such as
+/// `budget.max_cost`, and values are JSON, or else strings.
+///
+/// MCP servers are only set whole, as a JSON array in `mcp_servers`: each one
+/// replaces the server with the same name, so a single field can't be set.
+pub fn parse_overrides(settings: &[String], references: References) -> Result<Config, Error> {
+    let path = Path::new("--config");
+    let invalid = |problem: String| Error::ConfigError {
+        path: path.to_path_buf(),
+        problems: vec![problem],
+    };
+    let mut value = Value::Object(Default::default());
+    for setting in settings {
+        let Some((key, raw)) = setting.split_once('=') else {
+            return Err(invalid(format!("expected key=value, found {setting:?}")));
+        };
+        if key.starts_with("mcp_servers.") {
+            return Err(invalid(format!(
+                "{key}: MCP servers can only be set whole, \
+                 e.g. mcp_servers='[{{\"name\": \"github\", \"command\": \"github-mcp-server\"}}]'"
+            )));
+        }
+        let mut target = &mut value;
+        for name in key.split('.') {
+            if !target.is_object() {
+                *target = Value::Object(Default::default());
+            }
+            target = target
+                .as_object_mut()
+                .unwrap()
+                .entry(name)
+                .or_insert(Value::Null);
+        }
+        *target = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+    }
+    let interpolation = Interpolation {
+        keys: &|_| None,
+        trusted: true,
+        references,
+    };
+    config_from_value(path, value, &interpolation)
+}
+
+/// The user-level Enchant directory, `~/.enchant`.
+pub fn user_dir() -> PathBuf {
+    env::home_dir().unwrap().join(".enchant")
+}
+
+/// Loads the user's config files. With `References::Keep`, no `${cmd:...}`
+/// command is run and references are left as written.
+pub async fn load_config(references: References) -> Result<ConfigState, Error> {
+    let enchant_dir = check_directory(user_dir()).await?;
+    // Lets `enchant.json` use `"$schema": "./enchant.schema.json"`.
+    let schema = serde_json::to_string_pretty(&config_schema())?;
+    fs::write(enchant_dir.join("enchant.schema.json"), schema).await?;
+
+    // An invalid file is reported and ignored, rather than failing the start.
+    let mut warnings = vec![];
+
+    // API keys come first, so enchant.json can refer to them.
+    let no_keys = Interpolation {
+        keys: &|_| None,
+        trusted: true,
+        references,
+    };
+    let api_keys: ProviderKeys = or_warn(
+        check_config_json(enchant_dir.join(API_KEYS_FILE), &no_keys).await,
+        &mut warnings,
+    );
+
+    let lookup = key_lookup(&api_keys);
+    let interpolation = Interpolation {
+        keys: &lookup,
+        ..no_keys
+    };
+    let base: Config = or_warn(
+        check_config_json(enchant_dir.join("enchant.json"), &interpolation).await, // ~/.enchant/.enchant.json
+        &mut warnings,
+    );
+    drop(lookup);
+
+    let models: ModelInfoMap =
+        or_warn(check_json(enchant_dir.join("models.json")).await, &mut warnings);
+
+    Ok(ConfigState {
+        base,
+        models,
+        api_keys,
+        overrides: Config::default(),
+        warnings,
+        references,
+    })
+}
+
+fn or_warn<T: Default>(result: Result<T, Error>, warnings: &mut Vec<String>) -> T {
+    result.unwrap_or_else(|err| {
+        warnings.push(format!("{err}\nThe file is ignored."));
+        T::default()
+    })
+}
+
+/// Checks the config files that exist: the user's `enchant.json` and
+/// `api-keys.json`, and the `enchant.json` and `enchant.local.json` of
+/// `working_directory`. References are checked, but no command is run.
+pub async fn validate_config_files(working_directory: &Path) -> Vec<(PathBuf, Result<(), Error>)> {
+    async fn check<T: DeserializeOwned + JsonSchema>(
+        path: &Path,
+        interpolation: &Interpolation<'_>,
+    ) -> Option<Result<T, Error>> {
+        let content = fs::read_to_string(path).await.ok()?;
+        Some(parse_config(path, &content, interpolation))
+    }
+
+    let mut results = vec![];
+    let keys_path = user_dir().join(API_KEYS_FILE);
+    let no_keys = Interpolation {
+        keys: &|_| None,
+        trusted: true,
+        references: References::Keep,
+    };
+    let keys = match check::<ProviderKeys>(&keys_path, &no_keys).await {
+        Some(result) => {
+            let keys = result.as_ref().cloned().unwrap_or_default();
+            results.push((keys_path, result.map(|_| ())));
+            keys
+        }
+        None => ProviderKeys::new(),
+    };
+    let lookup = key_lookup(&keys);
+    for (path, trusted) in [
+        (user_dir().join("enchant.json"), true),
+        (working_directory.join("enchant.json"), false),
+        (working_directory.join("enchant.local.json"), false),
+    ] {
+        let interpolation = Interpolation {
+            keys: &lookup,
+            trusted,
+            references: References::Keep,
+        };
+        if let Some(result) = check::<Config>(&path, &interpolation).await {
+            results.push((path, result.map(|_| ())));
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_schema() {
+        // The published schema is regenerated with `enchant config schema`.
+        let published: Value =
+            serde_json::from_str(include_str!("../../docs/enchant.schema.json")).unwrap();
+        assert_eq!(published, config_schema());
+
+        let default = serde_json::to_value(Config::default()).unwrap();
+        assert!(validate(&config_schema(), &default).is_empty());
+
+        let path = Path::new("enchant.json");
+        let config = r#"{ "$schema": "./enchant.schema.json", "default_model": "sonnet" }"#;
+        let user = Interpolation {
+            keys: &|_| None,
+            trusted: true,
+            references: References::Resolve,
+        };
+        let config: Config = parse_config(path, config, &user).unwrap();
+        assert_eq!(config.default_model.as_deref(), Some("sonnet"));
+
+        let err = parse_config::<Config>(path, r#"{ "budget": { "max_cost": "5" } }"#, &user)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid enchant.json: budget.max_cost: expected number or null, found string"
+        );
+
+        let project = Interpolation {
+            trusted: false,
+            ..user
+        };
+        let config = r#"{ "default_model": "${cmd:echo sonnet}" }"#;
+        let err = parse_config::<Config>(path, config, &project).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid enchant.json: `${cmd:echo sonnet}` can only be used in the user's config files (at default_model)"
+        );
+        let kept = Interpolation {
+            references: References::Keep,
+            ..user
+        };
+        let config: Config = parse_config(path, config, &kept).unwrap();
+        assert_eq!(config.default_model.as_deref(), Some("${cmd:echo sonnet}"));
+
+        let keys = r#"{ "openai": "sk-secret" }"#;
+        let err = parse_config::<ProviderKeys>(Path::new(API_KEYS_FILE), keys, &user).unwrap_err();
+        assert!(!err.to_string().contains("sk-secret"));
+    }
+
+    #[test]
+    fn test_layers() {
+        let layer = |origin: &str, config| ConfigLayer {
+            origin: origin.to_string(),
+            config: serde_json::from_value(config).unwrap(),
+        };
+        let layers = [
+            layer(
+                "user",
+                json!({
+                    "default_model": "sonnet",
+                    "mcp_servers": [{ "name": "gh", "command": "gh-mcp", "args": ["stdio"] }],
+                    "permissions": { "bash": { "allow": ["ls"] } }
+                }),
+            ),
+            layer(
+                "project",
+                json!({
+                    "mcp_servers": [{ "name": "gh", "command": "gh-local" }, { "name": "db", "command": "db-mcp" }],
+                    "permissions": { "bash": { "allow": ["cargo test"] } }
+                }),
+            ),
+            ConfigLayer {
+                origin: COMMAND_LINE.to_string(),
+                config: parse_overrides(&["default_model=opus".to_string()], References::Resolve).unwrap(),
+            },
+        ];
+
+        let config = merge_layers(&layers);
+        let servers: Vec<_> = config.mcp_servers.iter().map(|s| s.command.as_str()).collect();
+        assert_eq!(servers, ["gh-local", "db-mcp"]);
+        assert!(config.mcp_servers[0].args.is_empty());
+
+        let settings = effective_settings(&layers);
+        let origins = |key: &str| {
+            let setting = settings.iter().find(|s| s.key == key).unwrap();
+            setting.origins.join(", ")
+        };
+        assert_eq!(origins("default_model"), COMMAND_LINE);
+        assert_eq!(origins("mcp_servers.gh.command"), "project");
+        assert_eq!(origins("permissions.bash.allow"), "user, project");
+        assert!(!settings.iter().any(|s| s.key == "mcp_servers.gh.args"));
+
+        assert!(parse_overrides(&["budget.max_cost=five".to_string()], References::Resolve).is_err());
+        assert!(parse_overrides(&["default_model".to_string()], References::Resolve).is_err());
+        let err = parse_overrides(&["mcp_servers.gh.command=gh".to_string()], References::Resolve)
+            .unwrap_err();
+        assert!(err.to_string().contains("MCP servers can only be set whole"));
+        let servers = r#"mcp_servers=[{"name": "gh", "command": "gh"}]"#.to_string();
+        let config = parse_overrides(&[servers], References::Resolve).unwrap();
+        assert_eq!(config.mcp_servers[0].command, "gh");
+    }
 }
@@ -0,0 +1,119 @@
+//! Conversation persistence, so sessions can be resumed with `/resume`.
+//!
+//! Each session is stored as `~/.enchant/sessions/<id>.json`.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use genai::chat::{ChatMessage, ChatRole};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{
+    agent::{
+        Session,
+        config::{check_directory, user_dir},
+    },
+    error::Error,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub id: String,
+    pub working_directory: PathBuf,
+    pub model: String,
+    /// Unix timestamp (seconds) of the last save.
+    pub updated_at: u64,
+    pub messages: Vec<ChatMessage>,
+}
+
+impl SavedSession {
+    /// The first user message, used to identify the session in listings.
+    pub fn title(&self) -> String {
+        self.messages
+            .iter()
+            .find(|m| matches!(m.role, ChatRole::User))
+            .and_then(|m| m.content.joined_texts())
+            .map(|t| t.lines().next().unwrap_or("").to_string())
+            .unwrap_or_default()
+    }
+}
+
+pub fn sessions_dir() -> PathBuf {
+    user_dir().join("sessions")
+}
+
+pub fn new_session_id() -> String {
+    format!("{:x}", now().as_millis())
+}
+
+fn now() -> std::time::Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Saves the session, unless the user has not said anything yet.
+pub async fn save_session(session: &Session) -> Result<(), Error> {
+    if !session
+        .messages
+        .iter()
+        .any(|m| matches!(m.role, ChatRole::User))
+    {
+        return Ok(());
+    }
+
+    let saved = SavedSession {
+        id: session.id.clone(),
+        working_directory: session.working_directory.clone(),
+        model: session.model.clone(),
+        updated_at: now().as_secs(),
+        messages: session.messages.clone(),
+    };
+    let dir = check_directory(sessions_dir()).await?;
+    fs::write(
+        dir.join(format!("{}.json", saved.id)),
+        serde_json::to_string(&saved)?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Lists saved sessions for a working directory, most recent first.
+pub async fn list_sessions(working_directory: &Path) -> Result<Vec<SavedSession>, Error> {
+    let mut sessions = vec![];
+    let Ok(mut entries) = fs::read_dir(sessions_dir()).await else {
+        return Ok(sessions);
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(content) = fs::read_to_string(entry.path()).await else {
+            continue;
+        };
+        // Skip files from other versions or that are half-written.
+        if let Ok(saved) = serde_json::from_str::<SavedSession>(&content)
+            && saved.working_directory == working_directory
+        {
+            sessions.push(saved);
+        }
+    }
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+    Ok(sessions)
+}
+
+pub async fn load_session(id: &str) -> Result<SavedSession, Error> {
+    let content = fs::read_to_string(sessions_dir().join(format!("{id}.json")))
+        .await
+        .map_err(|_| Error::CommandError(format!("No saved session with id {id}")))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Formats how long ago a unix timestamp was, e.g. "5m ago".
+pub fn format_age(timestamp: u64) -> String {
+    let secs = now().as_secs().saturating_sub(timestamp);
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
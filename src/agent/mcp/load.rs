@@ -1,51 +1,75 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashSet, sync::Arc};
 
-use tokio::sync::Mutex;
+use futures::future::join_all;
 
-use crate::agent::tools::{tool::WrappedTool, tool_error::ToolError};
+use crate::agent::tools::tool::WrappedTool;
 
-use super::{tool::McpTool, McpClient, McpServerConfig};
+use super::{
+    McpServer, McpServerConfig, McpServers, McpToolDef,
+    resources::{ListMcpResources, ReadMcpResource},
+    tool::McpTool,
+};
 
-/// Spawn all configured MCP servers and return their tools as `WrappedTool`s.
+/// Start all configured MCP servers concurrently and return them with their
+/// tools as `WrappedTool`s.
+///
+/// A server that fails to start, or doesn't start in time, is kept with its
+/// failure as status, so it can be reconnected later. If any server has
+/// resources, the `ListMcpResources` and `ReadMcpResource` tools are added as
+/// well; otherwise they are added once a server with resources connects.
 pub async fn load_mcp_tools(
-    servers: &[McpServerConfig],
-) -> Result<Vec<Box<dyn WrappedTool + Send + Sync + 'static>>, ToolError> {
-    let mut out: Vec<Box<dyn WrappedTool + Send + Sync + 'static>> = vec![];
-
-    // Note: sequential startup is simpler and avoids interleaved stdout parsing.
-    for cfg in servers {
-        if cfg.name.trim().is_empty() {
-            return Err(ToolError::Error {
-                message: "MCP server config missing 'name'".to_string(),
-            });
-        }
-        if cfg.command.trim().is_empty() {
-            return Err(ToolError::Error {
-                message: format!("MCP server '{}' missing 'command'", cfg.name),
-            });
+    configs: &[McpServerConfig],
+) -> (
+    McpServers,
+    Vec<Box<dyn WrappedTool + Send + Sync + 'static>>,
+) {
+    let servers: Vec<Arc<McpServer>> = configs
+        .iter()
+        .map(|cfg| Arc::new(McpServer::new(cfg.clone())))
+        .collect();
+    let results = join_all(servers.iter().map(|server| async move {
+        if server.config.disabled {
+            return Ok(vec![]);
         }
+        server.connect().await
+    }))
+    .await;
 
-        let client = McpClient::spawn(cfg).await?;
-        let client = Arc::new(Mutex::new(client));
-
-        let defs = {
-            let mut guard = client.lock().await;
-            guard.list_tools().await?
-        };
-
-        // Dedupe by fq name (server+tool)
-        let mut seen: HashMap<String, ()> = HashMap::new();
-
-        for def in defs {
-            let tool = McpTool::new(def, cfg, client.clone());
-            let fq = tool.to_tool().name;
-            if seen.contains_key(&fq) {
-                continue;
-            }
-            seen.insert(fq, ());
-            out.push(Box::new(tool));
+    let mut out = vec![];
+    let mut clients = McpServers::default();
+    for (server, result) in servers.into_iter().zip(results) {
+        if let Ok(defs) = result {
+            out.extend(mcp_tools(&server, defs));
         }
+        clients.insert(server);
+    }
+
+    if clients.clients().iter().any(|c| c.supports_resources()) {
+        out.extend(resource_tools(&clients));
     }
 
-    Ok(out)
+    (clients, out)
+}
+
+/// The `ListMcpResources` and `ReadMcpResource` tools, over all of `servers`.
+pub fn resource_tools(servers: &McpServers) -> Vec<Box<dyn WrappedTool + Send + Sync + 'static>> {
+    vec![
+        Box::new(ListMcpResources::new(servers.clone())),
+        Box::new(ReadMcpResource::new(servers.clone())),
+    ]
+}
+
+/// The tools of a server, as `WrappedTool`s, leaving out the excluded ones.
+pub fn mcp_tools(
+    server: &Arc<McpServer>,
+    defs: Vec<McpToolDef>,
+) -> Vec<Box<dyn WrappedTool + Send + Sync + 'static>> {
+    // Dedupe by fq name (server+tool)
+    let mut seen = HashSet::new();
+    defs.into_iter()
+        .filter(|def| server.config.is_tool_enabled(&def.name))
+        .map(|def| McpTool::new(def, server.clone()))
+        .filter(|tool| seen.insert(tool.to_tool().name))
+        .map(|tool| Box::new(tool) as Box<dyn WrappedTool + Send + Sync + 'static>)
+        .collect()
 }
@@ -1,224 +1,1159 @@
-use std::{collections::HashMap, process::Stdio};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    process::Stdio,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
 
-use serde::{Deserialize, Serialize};
-use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    process::{Child, Command},
+use rmcp::{
+    ClientHandler, RoleClient, ServiceError, ServiceExt,
+    model::{
+        CallToolRequest, CallToolRequestParam, CallToolResult, ClientInfo, ClientRequest,
+        GetPromptRequestParam, GetPromptResult, Implementation, Prompt, RawContent, ReadResourceRequestParam, Resource, ResourceContents,
+        ResourceTemplate, ServerResult, Tool,
+    },
+    service::{NotificationContext, PeerRequestOptions, RunningService},
+    transport::{
+        IntoTransport, SseClientTransport, StreamableHttpClientTransport, TokioChildProcess,
+        sse_client::SseClientConfig, streamable_http_client::StreamableHttpClientTransportConfig,
+    },
 };
+use genai::chat::{Binary, BinarySource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
 
-use crate::agent::tools::tool_error::ToolError;
+use crate::{
+    agent::{
+        config::user_dir,
+        tools::{permission::Permission, tool::ToolOutput, tool_error::ToolError},
+    },
+    util::env::redact,
+};
 
 pub mod load;
+pub mod resources;
+pub mod serve;
 pub mod tool;
 
-/// MCP stdio server configuration.
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+/// Time a server has to start and list its tools, unless configured otherwise.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tool calls running longer than this are cancelled, unless configured otherwise.
+const CALL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Times a server whose process has exited is restarted before giving up.
+const MAX_RESTARTS: u32 = 3;
+
+/// How Enchant talks to an MCP server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum McpTransport {
+    /// A child process, over stdin and stdout.
+    #[default]
+    Stdio,
+    /// The legacy HTTP transport with server-sent events.
+    Sse,
+    StreamableHttp,
+}
+
+/// MCP server configuration.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct McpServerConfig {
     pub name: String,
+    #[serde(default)]
+    pub transport: McpTransport,
+    /// Command of a `stdio` server.
+    #[serde(default)]
     pub command: String,
     #[serde(default)]
     pub args: Vec<String>,
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Endpoint of an `sse` or `streamable-http` server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Headers sent to an `sse` or `streamable-http` server.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
     /// Permission policy applied to all tools from this server.
     /// One of: "implicit", "allow_automatic", "require_approval", "never".
     #[serde(default)]
-    pub permission: Option<String>,
+    pub permission: Option<Permission>,
+    /// Permission of individual tools, overriding `permission`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tool_permissions: HashMap<String, Permission>,
+    /// Tools that only read, and so run without approval.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub read_only: Vec<String>,
+    /// Let tools the server annotates as read-only run without approval.
+    #[serde(default)]
+    pub trust_annotations: bool,
+    /// Only these tools are offered to the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    /// These tools are never offered to the model.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Don't start the server. It can still be connected from `/mcp`.
+    #[serde(default)]
+    pub disabled: bool,
+    /// Seconds the server has to start and list its tools.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_secs: Option<u64>,
+    /// Seconds after which a tool call is cancelled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_timeout_secs: Option<u64>,
+}
+
+impl McpServerConfig {
+    /// The permission of one of the server's tools: its entry in
+    /// `tool_permissions`, then `read_only`, then the server's `permission`.
+    ///
+    /// Without any of these, tools annotated as read-only run without
+    /// approval if the server has `trust_annotations`. Tools annotated as
+    /// destructive need approval even if the server's `permission` would
+    /// allow them.
+    pub fn tool_permission(&self, tool: &McpToolDef) -> Permission {
+        if let Some(permission) = self.tool_permissions.get(&tool.name) {
+            return permission.clone();
+        }
+        if self.read_only.contains(&tool.name) {
+            return Permission::Implicit;
+        }
+        match &self.permission {
+            Some(Permission::Implicit | Permission::AllowAutomatic) if tool.destructive => {
+                Permission::RequireApproval
+            }
+            Some(permission) => permission.clone(),
+            None if self.trust_annotations && tool.read_only && !tool.destructive => {
+                Permission::Implicit
+            }
+            None => Permission::RequireApproval,
+        }
+    }
+
+    /// Whether a tool is offered to the model, according to `include` and `exclude`.
+    pub fn is_tool_enabled(&self, tool: &str) -> bool {
+        let included = match &self.include {
+            Some(include) => include.iter().any(|t| t == tool),
+            None => true,
+        };
+        included && !self.exclude.iter().any(|t| t == tool)
+    }
+
+    fn connect_timeout(&self) -> Duration {
+        self.connect_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(CONNECT_TIMEOUT)
+    }
+
+    fn call_timeout(&self) -> Duration {
+        self.call_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(CALL_TIMEOUT)
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct McpToolDef {
     pub server: String,
     pub name: String,
+    /// Human-readable name, used in the transcript.
+    pub title: Option<String>,
     pub description: Option<String>,
     pub input_schema: serde_json::Value,
+    /// The server says the tool doesn't modify anything.
+    pub read_only: bool,
+    /// The server says the tool may delete or overwrite things.
+    pub destructive: bool,
 }
 
-/// Minimal MCP stdio client (JSON-RPC over newline-delimited stdio).
+impl McpToolDef {
+    fn new(server: &str, tool: Tool) -> Self {
+        let annotations = tool.annotations.unwrap_or_default();
+        Self {
+            server: server.to_string(),
+            name: tool.name.to_string(),
+            title: tool.title.or(annotations.title),
+            description: tool.description.map(|d| d.to_string()),
+            input_schema: serde_json::Value::Object((*tool.input_schema).clone()),
+            read_only: annotations.read_only_hint == Some(true),
+            destructive: annotations.destructive_hint == Some(true),
+        }
+    }
+}
+
+/// Tools listed by a server that haven't been added to the toolset yet.
+type ToolUpdates = Arc<Mutex<Option<Vec<McpToolDef>>>>;
+
+/// Identifies Enchant to MCP servers during initialization, and lists the
+/// tools again when a server says they have changed.
+#[derive(Clone, Debug, Default)]
+struct McpHandler {
+    server_name: String,
+    tool_updates: ToolUpdates,
+}
+
+impl ClientHandler for McpHandler {
+    async fn on_tool_list_changed(&self, context: NotificationContext<RoleClient>) {
+        if let Ok(tools) = context.peer.list_all_tools().await {
+            let tools = tools
+                .into_iter()
+                .map(|tool| McpToolDef::new(&self.server_name, tool))
+                .collect();
+            *self.tool_updates.lock().unwrap() = Some(tools);
+        }
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        ClientInfo {
+            client_info: Implementation {
+                name: "enchant".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Implementation::default()
+            },
+            ..ClientInfo::default()
+        }
+    }
+}
+
+/// MCP client backed by `rmcp`, which negotiates the protocol version and
+/// capabilities, and answers pings and notifications from the server.
 ///
-/// Supported subset:
-/// - initialize / initialized
-/// - tools/list
-/// - tools/call
+/// Requests can be made concurrently.
 pub struct McpClient {
     server_name: String,
-    child: Child,
-    stdin: tokio::process::ChildStdin,
-    stdout: BufReader<tokio::process::ChildStdout>,
-    next_id: u64,
+    service: RunningService<RoleClient, McpHandler>,
+    call_timeout: Duration,
+    tool_updates: ToolUpdates,
+    /// A problem that doesn't stop the server from working, such as its log
+    /// file not opening.
+    warning: Option<String>,
 }
 
 impl McpClient {
-    pub async fn spawn(cfg: &McpServerConfig) -> Result<Self, ToolError> {
-        let mut cmd = Command::new(&cfg.command);
-        cmd.args(&cfg.args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit());
-
-        for (k, v) in &cfg.env {
-            cmd.env(k, v);
-        }
-
-        let mut child = cmd.spawn()?;
-        let stdin = child.stdin.take().ok_or(ToolError::Error {
-            message: "Failed to open stdin for MCP server".to_string(),
-        })?;
-        let stdout = child.stdout.take().ok_or(ToolError::Error {
-            message: "Failed to open stdout for MCP server".to_string(),
-        })?;
-
-        let mut client = Self {
-            server_name: cfg.name.clone(),
-            child,
-            stdin,
-            stdout: BufReader::new(stdout),
-            next_id: 1,
-        };
-
-        client.initialize().await?;
-        Ok(client)
+    /// Starts or connects to the server described by `cfg`.
+    pub async fn start(cfg: &McpServerConfig) -> Result<Self, ToolError> {
+        let client = match cfg.transport {
+            McpTransport::Stdio => {
+                let mut cmd = Command::new(&cfg.command);
+                cmd.args(&cfg.args).envs(&cfg.env);
+                let (stderr, warning) = match stderr_log(&cfg.name) {
+                    Ok(file) => (file.into(), None),
+                    Err(err) => (
+                        Stdio::null(),
+                        Some(format!(
+                            "MCP server '{}': its stderr is discarded, since its log file can't be opened: {err}",
+                            cfg.name
+                        )),
+                    ),
+                };
+                let (process, _) = TokioChildProcess::builder(cmd).stderr(stderr).spawn()?;
+                let client = Self::connect(&cfg.name, process).await?;
+                Ok(Self { warning, ..client })
+            }
+            McpTransport::Sse => {
+                let config = SseClientConfig {
+                    sse_endpoint: server_url(cfg)?.into(),
+                    ..Default::default()
+                };
+                let transport = SseClientTransport::start_with_client(http_client(cfg)?, config)
+                    .await
+                    .map_err(|e| ToolError::Error {
+                        message: format!("Failed to connect to MCP server '{}': {e}", cfg.name),
+                    })?;
+                Self::connect(&cfg.name, transport).await
+            }
+            McpTransport::StreamableHttp => {
+                let config = StreamableHttpClientTransportConfig::with_uri(server_url(cfg)?);
+                let transport =
+                    StreamableHttpClientTransport::with_client(http_client(cfg)?, config);
+                Self::connect(&cfg.name, transport).await
+            }
+        }?;
+        Ok(Self {
+            call_timeout: cfg.call_timeout(),
+            ..client
+        })
     }
 
-    async fn write_jsonrpc(&mut self, method: &str, params: serde_json::Value) -> Result<u64, ToolError> {
-        let id = self.next_id;
-        self.next_id += 1;
+    /// Initializes a session with a server over `transport`.
+    pub async fn connect<T, E, A>(server_name: &str, transport: T) -> Result<Self, ToolError>
+    where
+        T: IntoTransport<RoleClient, E, A>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let handler = McpHandler {
+            server_name: server_name.to_string(),
+            tool_updates: ToolUpdates::default(),
+        };
+        let tool_updates = handler.tool_updates.clone();
+        let service = handler
+            .serve(transport)
+            .await
+            .map_err(|e| ToolError::Error {
+                message: format!("Failed to initialize MCP server '{server_name}': {e}"),
+            })?;
+        Ok(Self {
+            server_name: server_name.to_string(),
+            service,
+            call_timeout: CALL_TIMEOUT,
+            tool_updates,
+            warning: None,
+        })
+    }
 
-        let req = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "method": method,
-            "params": params,
-        });
+    pub fn name(&self) -> &str {
+        &self.server_name
+    }
 
-        let mut line = serde_json::to_vec(&req).map_err(|e| ToolError::Error {
-            message: format!("MCP serialize error: {e}"),
-        })?;
-        line.push(b'\n');
+    /// A problem found while starting the server that it runs despite.
+    pub fn warning(&self) -> Option<&str> {
+        self.warning.as_deref()
+    }
 
-        self.stdin.write_all(&line).await?;
-        self.stdin.flush().await?;
-        Ok(id)
+    /// Whether the server declared the resources capability.
+    pub fn supports_resources(&self) -> bool {
+        self.service
+            .peer_info()
+            .is_some_and(|info| info.capabilities.resources.is_some())
     }
 
-    async fn read_response(&mut self, id: u64) -> Result<serde_json::Value, ToolError> {
-        loop {
-            let mut buf = String::new();
-            let n = self.stdout.read_line(&mut buf).await?;
-            if n == 0 {
-                return Err(ToolError::Error {
-                    message: format!("MCP server '{}' closed stdout", self.server_name),
-                });
-            }
+    /// Whether the server declared the prompts capability.
+    pub fn supports_prompts(&self) -> bool {
+        self.service
+            .peer_info()
+            .is_some_and(|info| info.capabilities.prompts.is_some())
+    }
 
-            let v: serde_json::Value = serde_json::from_str(&buf).map_err(|e| ToolError::Error {
-                message: format!("MCP parse error: {e}. line={buf:?}"),
-            })?;
+    fn error(&self, err: ServiceError) -> ToolError {
+        ToolError::Error {
+            message: format!("MCP error from '{}': {err}", self.server_name),
+        }
+    }
 
-            if v.get("id").and_then(|x| x.as_u64()) != Some(id) {
-                continue;
-            }
+    pub async fn list_tools(&self) -> Result<Vec<McpToolDef>, ToolError> {
+        let tools = self
+            .service
+            .list_all_tools()
+            .await
+            .map_err(|e| self.error(e))?;
+        Ok(tools
+            .into_iter()
+            .map(|tool| McpToolDef::new(&self.server_name, tool))
+            .collect())
+    }
+
+    /// Whether the connection is gone, e.g. because the server process exited.
+    pub fn is_closed(&self) -> bool {
+        self.service.is_transport_closed()
+    }
+
+    /// The tools the server listed since this was last called, if they changed.
+    pub fn take_tool_updates(&self) -> Option<Vec<McpToolDef>> {
+        self.tool_updates.lock().unwrap().take()
+    }
+
+    pub async fn list_prompts(&self) -> Result<Vec<Prompt>, ToolError> {
+        self.service
+            .list_all_prompts()
+            .await
+            .map_err(|e| self.error(e))
+    }
+
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<GetPromptResult, ToolError> {
+        let arguments = arguments
+            .into_iter()
+            .map(|(k, v)| (k, serde_json::Value::String(v)))
+            .collect();
+        self.service
+            .get_prompt(GetPromptRequestParam {
+                name: name.to_string(),
+                arguments: Some(arguments),
+            })
+            .await
+            .map_err(|e| self.error(e))
+    }
+
+    pub async fn list_resources(&self) -> Result<Vec<Resource>, ToolError> {
+        self.service
+            .list_all_resources()
+            .await
+            .map_err(|e| self.error(e))
+    }
+
+    pub async fn list_resource_templates(&self) -> Result<Vec<ResourceTemplate>, ToolError> {
+        self.service
+            .list_all_resource_templates()
+            .await
+            .map_err(|e| self.error(e))
+    }
+
+    /// Reads a resource as text. Binary contents are described.
+    pub async fn read_resource(&self, uri: &str) -> Result<String, ToolError> {
+        let result = self
+            .service
+            .read_resource(ReadResourceRequestParam {
+                uri: uri.to_string(),
+            })
+            .await
+            .map_err(|e| self.error(e))?;
+        Ok(result
+            .contents
+            .iter()
+            .map(|contents| match contents {
+                ResourceContents::TextResourceContents { text, .. } => text.clone(),
+                ResourceContents::BlobResourceContents { uri, mime_type, .. } => format!(
+                    "[resource: {uri}, {}]",
+                    mime_type.as_deref().unwrap_or("binary")
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
 
-            if let Some(err) = v.get("error") {
+    pub async fn call_tool(
+        &self,
+        tool_name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<ToolOutput, ToolError> {
+        let arguments = match arguments {
+            serde_json::Value::Object(map) => Some(map),
+            serde_json::Value::Null => None,
+            other => {
                 return Err(ToolError::Error {
-                    message: format!("MCP error from '{}': {}", self.server_name, err),
+                    message: format!("MCP tool arguments must be an object, got {other}"),
                 });
             }
+        };
+        let request = ClientRequest::CallToolRequest(CallToolRequest::new(CallToolRequestParam {
+            name: tool_name.to_string().into(),
+            arguments,
+        }));
+        // On timeout, the server is told to cancel the call.
+        let options = PeerRequestOptions {
+            timeout: Some(self.call_timeout),
+            meta: None,
+        };
+        let handle = self
+            .service
+            .send_cancellable_request(request, options)
+            .await
+            .map_err(|e| self.error(e))?;
+        let result = match handle.await_response().await.map_err(|e| self.error(e))? {
+            ServerResult::CallToolResult(result) => result,
+            other => {
+                return Err(ToolError::Error {
+                    message: format!(
+                        "MCP '{}' returned an unexpected response: {other:?}",
+                        self.server_name
+                    ),
+                });
+            }
+        };
 
-            return Ok(v.get("result").cloned().unwrap_or(serde_json::Value::Null));
+        let output = tool_output(&result);
+        if result.is_error == Some(true) {
+            return Err(ToolError::Error {
+                message: output.content,
+            });
         }
+        Ok(output)
     }
+}
 
-    async fn initialize(&mut self) -> Result<(), ToolError> {
-        let id = self
-            .write_jsonrpc(
-                "initialize",
-                serde_json::json!({
-                    "protocolVersion": "2024-11-05",
-                    "clientInfo": { "name": "enchant", "version": env!("CARGO_PKG_VERSION") }
-                }),
-            )
-            .await?;
-        let _ = self.read_response(id).await?;
+/// Whether a configured server can be used.
+#[derive(Clone)]
+pub enum McpServerStatus {
+    Connected(Arc<McpClient>),
+    Failed(String),
+    Disabled,
+}
 
-        // Best-effort notification.
-        let _ = self.write_jsonrpc("initialized", serde_json::json!({})).await;
-        Ok(())
+/// A configured MCP server. Its client is replaced when it is reconnected, so
+/// the tools holding on to the server keep working.
+pub struct McpServer {
+    pub config: McpServerConfig,
+    status: RwLock<McpServerStatus>,
+    /// Held while the server is restarted, so that it is restarted only once.
+    restarting: tokio::sync::Mutex<()>,
+}
+
+impl McpServer {
+    pub fn new(config: McpServerConfig) -> Self {
+        let status = if config.disabled {
+            McpServerStatus::Disabled
+        } else {
+            McpServerStatus::Failed("Not started".to_string())
+        };
+        Self {
+            config,
+            status: RwLock::new(status),
+            restarting: tokio::sync::Mutex::new(()),
+        }
     }
 
-    pub async fn list_tools(&mut self) -> Result<Vec<McpToolDef>, ToolError> {
-        let id = self.write_jsonrpc("tools/list", serde_json::json!({})).await?;
-        let result = self.read_response(id).await?;
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
 
-        let tools = result
-            .get("tools")
-            .and_then(|t| t.as_array())
-            .ok_or(ToolError::Error {
-                message: format!("MCP '{}' tools/list returned unexpected shape: {result}", self.server_name),
-            })?;
+    pub fn status(&self) -> McpServerStatus {
+        self.status.read().unwrap().clone()
+    }
 
-        let mut defs = vec![];
-        for t in tools {
-            let name = t.get("name").and_then(|x| x.as_str()).ok_or(ToolError::Error {
-                message: format!("MCP '{}' tool missing name: {t}", self.server_name),
-            })?;
-            let description = t
-                .get("description")
-                .and_then(|x| x.as_str())
-                .map(|s| s.to_string());
-            let input_schema = t
-                .get("inputSchema")
-                .cloned()
-                .unwrap_or(serde_json::json!({ "type": "object" }));
-
-            defs.push(McpToolDef {
-                server: self.server_name.clone(),
-                name: name.to_string(),
-                description,
-                input_schema,
-            });
+    /// The client of a connected server.
+    pub fn client(&self) -> Result<Arc<McpClient>, ToolError> {
+        match self.status() {
+            McpServerStatus::Connected(client) => Ok(client),
+            McpServerStatus::Failed(reason) => Err(ToolError::Error {
+                message: format!("MCP server '{}' is not connected: {reason}", self.name()),
+            }),
+            McpServerStatus::Disabled => Err(ToolError::Error {
+                message: format!("MCP server '{}' is disabled", self.name()),
+            }),
         }
-        Ok(defs)
     }
 
-    pub async fn call_tool(&mut self, tool_name: &str, arguments: serde_json::Value) -> Result<String, ToolError> {
-        let id = self
-            .write_jsonrpc(
-                "tools/call",
-                serde_json::json!({
-                    "name": tool_name,
-                    "arguments": arguments,
-                }),
-            )
-            .await?;
-        let result = self.read_response(id).await?;
-
-        if let Some(content) = result.get("content") {
-            if let Some(arr) = content.as_array() {
-                let mut out = String::new();
-                for block in arr {
-                    if block.get("type").and_then(|x| x.as_str()) == Some("text") {
-                        if let Some(t) = block.get("text").and_then(|x| x.as_str()) {
-                            if !out.is_empty() {
-                                out.push('\n');
-                            }
-                            out.push_str(t);
-                        }
-                    }
+    /// The client of a connected server. If the server process has exited, it
+    /// is restarted first, with backoff, and its tools are listed again.
+    pub async fn live_client(&self) -> Result<Arc<McpClient>, ToolError> {
+        let client = self.client()?;
+        if !client.is_closed() {
+            return Ok(client);
+        }
+
+        let _restarting = self.restarting.lock().await;
+        let client = self.client()?;
+        if !client.is_closed() {
+            // Restarted by another call in the meantime.
+            return Ok(client);
+        }
+        let mut attempt = 0;
+        let tools = loop {
+            match self.connect().await {
+                Ok(tools) => break tools,
+                Err(err) if attempt + 1 >= MAX_RESTARTS => {
+                    return Err(ToolError::Error {
+                        message: format!(
+                            "MCP server '{}' exited and could not be restarted: {err}",
+                            self.name()
+                        ),
+                    });
                 }
-                if !out.is_empty() {
-                    return Ok(out);
+                Err(_) => {
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
                 }
             }
-            return Ok(content.to_string());
+        };
+        let client = self.client()?;
+        *client.tool_updates.lock().unwrap() = Some(tools);
+        Ok(client)
+    }
+
+    /// Starts the server, or restarts it if it is running, and lists its tools.
+    /// Gives up after the connect timeout.
+    pub async fn connect(&self) -> Result<Vec<McpToolDef>, ToolError> {
+        let timeout = self.config.connect_timeout();
+        let connect = async {
+            check_config(&self.config)?;
+            let client = McpClient::start(&self.config).await?;
+            let tools = client.list_tools().await?;
+            Ok::<_, ToolError>((client, tools))
+        };
+        let result = match tokio::time::timeout(timeout, connect).await {
+            Ok(result) => result,
+            Err(_) => Err(ToolError::Error {
+                message: format!(
+                    "MCP server '{}' did not start within {}s",
+                    self.name(),
+                    timeout.as_secs()
+                ),
+            }),
+        };
+
+        // Dropping the previous client shuts it down.
+        let mut status = self.status.write().unwrap();
+        match result {
+            Ok((client, tools)) => {
+                *status = McpServerStatus::Connected(Arc::new(client));
+                Ok(tools)
+            }
+            Err(err) => {
+                let reason = match &err {
+                    ToolError::Error { message } => message.clone(),
+                    other => other.to_string(),
+                };
+                *status = McpServerStatus::Failed(redact(&reason));
+                Err(err)
+            }
         }
+    }
+}
+
+/// The configured MCP servers, by name.
+#[derive(Clone, Default)]
+pub struct McpServers {
+    servers: BTreeMap<String, Arc<McpServer>>,
+}
+
+impl McpServers {
+    pub fn insert(&mut self, server: Arc<McpServer>) {
+        self.servers.insert(server.name().to_string(), server);
+    }
+
+    pub fn server(&self, name: &str) -> Option<&Arc<McpServer>> {
+        self.servers.get(name)
+    }
+
+    /// The servers, sorted by name.
+    pub fn servers(&self) -> impl Iterator<Item = &Arc<McpServer>> {
+        self.servers.values()
+    }
+
+    /// The clients of the connected servers, sorted by server name.
+    pub fn clients(&self) -> Vec<Arc<McpClient>> {
+        self.servers()
+            .filter_map(|server| server.client().ok())
+            .collect()
+    }
+}
+
+fn check_config(cfg: &McpServerConfig) -> Result<(), ToolError> {
+    if cfg.name.trim().is_empty() {
+        return Err(ToolError::Error {
+            message: "MCP server config missing 'name'".to_string(),
+        });
+    }
+    if cfg.transport == McpTransport::Stdio && cfg.command.trim().is_empty() {
+        return Err(ToolError::Error {
+            message: format!("MCP server '{}' missing 'command'", cfg.name),
+        });
+    }
+    Ok(())
+}
+
+/// Where the stderr of a `stdio` server goes: `~/.enchant/logs/mcp-<server>.log`,
+/// so that it doesn't end up on top of the terminal UI.
+fn stderr_log(server_name: &str) -> std::io::Result<File> {
+    let dir = user_dir().join("logs");
+    fs::create_dir_all(&dir)?;
+    File::options()
+        .create(true)
+        .append(true)
+        .open(dir.join(log_file_name(server_name)))
+}
+
+/// Path separators in the server's name are replaced, so that the log stays
+/// in the logs directory.
+fn log_file_name(server_name: &str) -> String {
+    let name: String = server_name
+        .chars()
+        .map(|c| if std::path::is_separator(c) { '_' } else { c })
+        .collect();
+    format!("mcp-{name}.log")
+}
+
+fn server_url(cfg: &McpServerConfig) -> Result<String, ToolError> {
+    cfg.url.clone().ok_or_else(|| ToolError::Error {
+        message: format!("MCP server '{}' missing 'url'", cfg.name),
+    })
+}
 
-        Ok(result.to_string())
+/// An HTTP client sending the configured headers with every request.
+fn http_client(cfg: &McpServerConfig) -> Result<reqwest::Client, ToolError> {
+    let invalid = |e: String| ToolError::Error {
+        message: format!("MCP server '{}': {e}", cfg.name),
+    };
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (name, value) in &cfg.headers {
+        headers.insert(
+            reqwest::header::HeaderName::try_from(name).map_err(|e| invalid(e.to_string()))?,
+            reqwest::header::HeaderValue::try_from(value).map_err(|e| invalid(e.to_string()))?,
+        );
     }
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|e| invalid(e.to_string()))
 }
 
-impl Drop for McpClient {
-    fn drop(&mut self) {
-        let _ = self.child.start_kill();
+/// Converts a tool result for the model.
+///
+/// Text is kept as is, and images are attached. Structured content is used
+/// when there is no text, and other content is described, since it can't be
+/// passed on as text.
+fn tool_output(result: &CallToolResult) -> ToolOutput {
+    let mut blocks = vec![];
+    let mut images = vec![];
+    for content in &result.content {
+        match &content.raw {
+            RawContent::Text(text) => blocks.push(text.text.clone()),
+            RawContent::Image(image) => {
+                blocks.push(format!("[image {}: {}]", images.len() + 1, image.mime_type));
+                images.push(Binary::new(
+                    image.mime_type.clone(),
+                    BinarySource::Base64(image.data.clone().into()),
+                    None,
+                ));
+            }
+            RawContent::Audio(audio) => {
+                blocks.push(format!(
+                    "[audio: {}, {} bytes of base64]",
+                    audio.mime_type,
+                    audio.data.len()
+                ));
+            }
+            RawContent::Resource(resource) => match &resource.resource {
+                ResourceContents::TextResourceContents { uri, text, .. } => {
+                    blocks.push(format!("Resource {uri}:\n{text}"));
+                }
+                ResourceContents::BlobResourceContents { uri, mime_type, .. } => {
+                    blocks.push(format!(
+                        "[resource: {uri}, {}]",
+                        mime_type.as_deref().unwrap_or("binary")
+                    ));
+                }
+            },
+            RawContent::ResourceLink(link) => {
+                blocks.push(format!("[resource link: {} ({})]", link.uri, link.name));
+            }
+        }
+    }
+
+    let has_text = result
+        .content
+        .iter()
+        .any(|c| matches!(c.raw, RawContent::Text(_)));
+    if !has_text && let Some(structured) = &result.structured_content {
+        blocks.push(serde_json::to_string_pretty(structured).unwrap_or(structured.to_string()));
+    }
+    ToolOutput::new(blocks.join("\n")).with_images(images)
+}
+
+#[cfg(test)]
+mod tests {
+    use rmcp::{
+        ErrorData, RoleServer, ServerHandler,
+        model::{
+            AnnotateAble, CallToolResult, Content, ListPromptsResult, ListResourcesResult,
+            ListToolsResult, PaginatedRequestParam, PromptArgument, PromptMessage,
+            PromptMessageRole, RawResource, ReadResourceResult, ServerCapabilities, ServerInfo,
+            Tool,
+        },
+        service::RequestContext,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    /// An in-process server with an `echo` tool, a `whoami` tool returning
+    /// the `Authorization` header when served over HTTP, a `refresh` tool
+    /// announcing that the tools changed, a `memo://greeting` resource
+    /// and a `review` prompt.
+    #[derive(Clone)]
+    struct EchoServer;
+
+    impl ServerHandler for EchoServer {
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo {
+                capabilities: ServerCapabilities::builder()
+                    .enable_tools()
+                    .enable_resources()
+                    .enable_prompts()
+                    .build(),
+                ..ServerInfo::default()
+            }
+        }
+
+        async fn list_tools(
+            &self,
+            _request: Option<PaginatedRequestParam>,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ListToolsResult, ErrorData> {
+            let schema =
+                json!({ "type": "object", "properties": { "text": { "type": "string" } } });
+            Ok(ListToolsResult::with_all_items(vec![Tool::new(
+                "echo",
+                "Echoes its input",
+                schema.as_object().unwrap().clone(),
+            )]))
+        }
+
+        async fn list_resources(
+            &self,
+            _request: Option<PaginatedRequestParam>,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ListResourcesResult, ErrorData> {
+            Ok(ListResourcesResult::with_all_items(vec![
+                RawResource::new("memo://greeting", "greeting").no_annotation(),
+            ]))
+        }
+
+        async fn read_resource(
+            &self,
+            request: ReadResourceRequestParam,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ReadResourceResult, ErrorData> {
+            match request.uri.as_str() {
+                "memo://greeting" => Ok(ReadResourceResult {
+                    contents: vec![ResourceContents::text("Hello!", request.uri.clone())],
+                }),
+                _ => Err(ErrorData::resource_not_found("No such resource", None)),
+            }
+        }
+
+        async fn list_prompts(
+            &self,
+            _request: Option<PaginatedRequestParam>,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ListPromptsResult, ErrorData> {
+            let file = PromptArgument {
+                name: "file".to_string(),
+                title: None,
+                description: None,
+                required: Some(true),
+            };
+            Ok(ListPromptsResult::with_all_items(vec![Prompt::new(
+                "review",
+                Some("Reviews a file"),
+                Some(vec![file]),
+            )]))
+        }
+
+        async fn get_prompt(
+            &self,
+            request: GetPromptRequestParam,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<GetPromptResult, ErrorData> {
+            let file = request.arguments.unwrap_or_default()["file"].clone();
+            Ok(GetPromptResult {
+                description: None,
+                messages: vec![
+                    PromptMessage::new_text(PromptMessageRole::Assistant, "I review carefully."),
+                    PromptMessage::new_text(
+                        PromptMessageRole::User,
+                        format!("Review {}", file.as_str().unwrap_or_default()),
+                    ),
+                ],
+            })
+        }
+
+        async fn call_tool(
+            &self,
+            request: CallToolRequestParam,
+            context: RequestContext<RoleServer>,
+        ) -> Result<CallToolResult, ErrorData> {
+            if request.name == "refresh" {
+                context.peer.notify_tool_list_changed().await.unwrap();
+                return Ok(CallToolResult::success(vec![Content::text("Refreshed")]));
+            }
+            if request.name == "whoami" {
+                let authorization = context
+                    .extensions
+                    .get::<hyper::http::request::Parts>()
+                    .and_then(|parts| parts.headers.get("authorization"))
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default();
+                return Ok(CallToolResult::success(vec![Content::text(authorization)]));
+            }
+            let arguments = serde_json::Value::Object(request.arguments.unwrap_or_default());
+            match arguments["text"].as_str() {
+                Some(text) => Ok(CallToolResult::success(vec![Content::text(text)])),
+                None => Ok(CallToolResult::structured_error(
+                    json!({ "missing": "text" }),
+                )),
+            }
+        }
+    }
+
+    fn connected(client: McpClient) -> Arc<McpServer> {
+        Arc::new(McpServer {
+            config: McpServerConfig {
+                name: client.name().to_string(),
+                ..Default::default()
+            },
+            status: RwLock::new(McpServerStatus::Connected(Arc::new(client))),
+            restarting: Default::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_client() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            let server = EchoServer.serve(server_io).await.unwrap();
+            server.waiting().await.unwrap();
+        });
+
+        let client = McpClient::connect("test", client_io).await.unwrap();
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools[0].name, "echo");
+        assert_eq!(tools[0].input_schema["type"], "object");
+
+        let output = client
+            .call_tool("echo", json!({ "text": "hi" }))
+            .await
+            .unwrap();
+        assert_eq!(output.content, "hi");
+
+        let err = client.call_tool("echo", json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("missing"));
+
+        assert!(client.take_tool_updates().is_none());
+        client.call_tool("refresh", json!({})).await.unwrap();
+        let mut updates = None;
+        for _ in 0..100 {
+            updates = client.take_tool_updates();
+            if updates.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(updates.unwrap()[0].name, "echo");
+    }
+
+    #[tokio::test]
+    async fn test_resources() {
+        use crate::agent::{Session, Toolset, config::Config};
+
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            let server = EchoServer.serve(server_io).await.unwrap();
+            server.waiting().await.unwrap();
+        });
+        let mut servers = McpServers::default();
+        servers.insert(connected(
+            McpClient::connect("memo", client_io).await.unwrap(),
+        ));
+
+        let (prompt, errors) = resources::expand_resource_mentions(
+            &servers,
+            "Reply to @memo:memo://greeting, and @memo:memo://missing. Mail me@example.com",
+        )
+        .await;
+        assert_eq!(
+            prompt.split_once("\n\n").unwrap().1,
+            "<resource server=\"memo\" uri=\"memo://greeting\">\nHello!\n</resource>"
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("@memo:memo://missing"));
+
+        // A server with resources that connects after startup brings the resource tools.
+        let mut session = Session::new_subagent(
+            Config::default(),
+            Default::default(),
+            "openai::gpt-4o".to_string(),
+            Toolset::new(vec![]),
+            String::new(),
+        )
+        .unwrap();
+        session.mcp = servers.clone();
+        let server = servers.server("memo").unwrap();
+        let defs = server.client().unwrap().list_tools().await.unwrap();
+        session.replace_mcp_tools(server, defs.clone());
+        session.replace_mcp_tools(server, defs);
+        let names: Vec<_> = session
+            .tools
+            .list_tools()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        for name in ["ListMcpResources", "ReadMcpResource"] {
+            assert_eq!(names.iter().filter(|n| *n == name).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_prompts() {
+        use crate::{
+            agent::{Session, Toolset, config::Config},
+            commands::{CommandOutcome, SlashCommand, mcp_prompt::load_mcp_prompts},
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        runtime.spawn(async move {
+            let server = EchoServer.serve(server_io).await.unwrap();
+            server.waiting().await.unwrap();
+        });
+        let mut servers = McpServers::default();
+        servers.insert(connected(
+            runtime
+                .block_on(McpClient::connect("code", client_io))
+                .unwrap(),
+        ));
+
+        let (commands, warnings) = runtime.block_on(load_mcp_prompts(&servers));
+        assert!(warnings.is_empty());
+        let info = commands[0].info();
+        assert_eq!(info.usage(), "/mcp__code__review <file>");
+        assert!(info.parse_args("").is_err());
+
+        let mut session = Session::new_subagent(
+            Config::default(),
+            Default::default(),
+            "openai::gpt-4o".to_string(),
+            Toolset::new(vec![]),
+            "prompt".to_string(),
+        )
+        .unwrap();
+        let args = info.parse_args("\"src/main.rs\"").unwrap();
+        let outcome = runtime
+            .block_on(commands[0].run(&mut session, args))
+            .unwrap();
+        let CommandOutcome::Prompt(prompt) = outcome else {
+            panic!("expected a prompt");
+        };
+        assert_eq!(prompt, "Review src/main.rs");
+        let last = session.messages.last().unwrap();
+        assert_eq!(last.content.first_text(), Some("I review carefully."));
+    }
+
+    #[test]
+    fn test_tool_config() {
+        let cfg: McpServerConfig = serde_json::from_value(json!({
+            "name": "github",
+            "command": "github-mcp",
+            "permission": "allow_automatic",
+            "tool_permissions": { "delete_repo": "never" },
+            "read_only": ["search_issues"],
+            "exclude": ["delete_repo"]
+        }))
+        .unwrap();
+        let tool = |name: &str| McpToolDef {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(cfg.tool_permission(&tool("delete_repo")), Permission::Never);
+        assert_eq!(cfg.tool_permission(&tool("search_issues")), Permission::Implicit);
+        assert_eq!(cfg.tool_permission(&tool("create_issue")), Permission::AllowAutomatic);
+        let destructive = McpToolDef {
+            destructive: true,
+            ..tool("close_issue")
+        };
+        assert_eq!(cfg.tool_permission(&destructive), Permission::RequireApproval);
+        let read_only = McpToolDef {
+            read_only: true,
+            ..tool("list_issues")
+        };
+        let unset = McpServerConfig::default();
+        assert_eq!(unset.tool_permission(&read_only), Permission::RequireApproval);
+        let trusted = McpServerConfig {
+            trust_annotations: true,
+            ..McpServerConfig::default()
+        };
+        assert_eq!(trusted.tool_permission(&read_only), Permission::Implicit);
+        assert_eq!(unset.tool_permission(&tool("create_issue")), Permission::RequireApproval);
+        assert!(!cfg.is_tool_enabled("delete_repo"));
+        assert!(cfg.is_tool_enabled("create_issue"));
+
+        let cfg = McpServerConfig {
+            include: Some(vec!["search_issues".to_string()]),
+            ..cfg
+        };
+        assert!(!cfg.is_tool_enabled("create_issue"));
+
+        let invalid = serde_json::from_value::<McpServerConfig>(json!({
+            "name": "github",
+            "permission": "ask"
+        }));
+        assert!(invalid.unwrap_err().to_string().contains("unknown variant `ask`"));
+    }
+
+    #[test]
+    fn test_log_file_name() {
+        assert_eq!(log_file_name("github"), "mcp-github.log");
+        assert_eq!(log_file_name("../team/db"), "mcp-.._team_db.log");
+    }
+
+    #[test]
+    fn test_tool_output() {
+        let result = CallToolResult::success(vec![
+            Content::text("A chart:"),
+            Content::image("aGVsbG8=", "image/png"),
+        ]);
+        let output = tool_output(&result);
+        assert_eq!(output.content, "A chart:\n[image 1: image/png]");
+        assert_eq!(output.images[0].content_type, "image/png");
+    }
+
+    #[tokio::test]
+    async fn test_startup_timeout() {
+        let configs: Vec<McpServerConfig> = serde_json::from_value(json!([
+            { "name": "slow", "command": "sleep", "args": ["10"], "connect_timeout_secs": 1 },
+            { "name": "slower", "command": "sleep", "args": ["20"], "connect_timeout_secs": 1 },
+            { "name": "broken" },
+            { "name": "off", "command": "sleep", "disabled": true },
+        ]))
+        .unwrap();
+
+        let started = std::time::Instant::now();
+        let (servers, tools) = load::load_mcp_tools(&configs).await;
+        // The servers are started concurrently.
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(tools.is_empty());
+
+        let status = |name| match servers.server(name).unwrap().status() {
+            McpServerStatus::Connected(_) => "connected".to_string(),
+            McpServerStatus::Failed(reason) => reason,
+            McpServerStatus::Disabled => "disabled".to_string(),
+        };
+        assert_eq!(status("slow"), "MCP server 'slow' did not start within 1s");
+        assert_eq!(status("slower"), "MCP server 'slower' did not start within 1s");
+        assert_eq!(status("broken"), "MCP server 'broken' missing 'command'");
+        assert_eq!(status("off"), "disabled");
+        assert!(servers.clients().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http() {
+        use std::{convert::Infallible, sync::Arc};
+
+        use hyper::{server::conn::http1, service::service_fn};
+        use hyper_util::rt::TokioIo;
+        use rmcp::transport::streamable_http_server::{
+            StreamableHttpServerConfig, StreamableHttpService, session::local::LocalSessionManager,
+        };
+
+        let service = StreamableHttpService::new(
+            || Ok(EchoServer),
+            Arc::new(LocalSessionManager::default()),
+            StreamableHttpServerConfig::default(),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let service = service.clone();
+                tokio::spawn(http1::Builder::new().serve_connection(
+                    TokioIo::new(stream),
+                    service_fn(move |request| {
+                        let service = service.clone();
+                        async move { Ok::<_, Infallible>(service.handle(request).await) }
+                    }),
+                ));
+            }
+        });
+
+        let cfg: McpServerConfig = serde_json::from_value(json!({
+            "name": "shared",
+            "transport": "streamable-http",
+            "url": format!("http://127.0.0.1:{port}/mcp"),
+            "headers": { "Authorization": "Bearer secret" }
+        }))
+        .unwrap();
+        let client = McpClient::start(&cfg).await.unwrap();
+        assert_eq!(client.list_tools().await.unwrap()[0].name, "echo");
+        let output = client.call_tool("whoami", json!({})).await.unwrap();
+        assert_eq!(output.content, "Bearer secret");
     }
 }
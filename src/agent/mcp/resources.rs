@@ -0,0 +1,219 @@
+//! MCP resources: tools for the agent to list and read them, and `@server:uri`
+//! mentions in prompts.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use genai::chat::Tool as AITool;
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::agent::{
+    Session,
+    tools::{
+        permission::Permission,
+        tool::{ToolPreview, WrappedTool},
+        tool_error::ToolError,
+    },
+};
+
+use super::{McpClient, McpServers};
+
+fn parse_input<T: serde::de::DeserializeOwned>(input: Value) -> Result<T, ToolError> {
+    serde_json::from_value(input).map_err(|e| ToolError::Error {
+        message: format!("Invalid tool input: {e}"),
+    })
+}
+
+async fn find_server(servers: &McpServers, name: &str) -> Result<Arc<McpClient>, ToolError> {
+    servers
+        .server(name)
+        .ok_or_else(|| ToolError::Error {
+            message: format!("Unknown MCP server '{name}'"),
+        })?
+        .live_client()
+        .await
+}
+
+/// Lists the resources and resource templates of the MCP servers.
+pub struct ListMcpResources {
+    servers: McpServers,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ListMcpResourcesInput {
+    /// Only list the resources of this server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+}
+
+impl ListMcpResources {
+    pub fn new(servers: McpServers) -> Self {
+        Self { servers }
+    }
+}
+
+#[async_trait]
+impl WrappedTool for ListMcpResources {
+    async fn call(&self, input: Value) -> Result<String, ToolError> {
+        let input: ListMcpResourcesInput = parse_input(input)?;
+        let clients: Vec<Arc<McpClient>> = match &input.server {
+            Some(name) => vec![find_server(&self.servers, name).await?],
+            None => self
+                .servers
+                .clients()
+                .into_iter()
+                .filter(|c| c.supports_resources())
+                .collect(),
+        };
+
+        let mut lines = vec![];
+        for client in clients {
+            for resource in client.list_resources().await? {
+                let mut line = format!("{}: {} ({})", client.name(), resource.uri, resource.name);
+                if let Some(description) = &resource.description {
+                    line.push_str(&format!(" - {description}"));
+                }
+                lines.push(line);
+            }
+            for template in client.list_resource_templates().await? {
+                let mut line = format!(
+                    "{}: {} ({}, template)",
+                    client.name(),
+                    template.uri_template,
+                    template.name
+                );
+                if let Some(description) = &template.description {
+                    line.push_str(&format!(" - {description}"));
+                }
+                lines.push(line);
+            }
+        }
+        if lines.is_empty() {
+            return Ok("No resources found.".to_string());
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn to_tool(&self) -> AITool {
+        AITool {
+            name: "ListMcpResources".to_string(),
+            description: Some(include_str!("./list_resources.md").to_string()),
+            schema: Some(schema_for!(ListMcpResourcesInput).to_value()),
+            config: None,
+        }
+    }
+
+    fn requires_permission(
+        &self,
+        _session: &Session,
+        _input: &Value,
+    ) -> Result<Permission, ToolError> {
+        Ok(Permission::Implicit)
+    }
+
+    fn describe_action(&self, input: &Value) -> String {
+        match input.get("server").and_then(|s| s.as_str()) {
+            Some(server) => format!("ListMcpResources({server})"),
+            None => "ListMcpResources".to_string(),
+        }
+    }
+
+    async fn generate_preview(&self, _input: &Value) -> Option<ToolPreview> {
+        None
+    }
+}
+
+/// Reads a resource from an MCP server.
+pub struct ReadMcpResource {
+    servers: McpServers,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ReadMcpResourceInput {
+    /// The server publishing the resource.
+    pub server: String,
+    /// The URI of the resource.
+    pub uri: String,
+}
+
+impl ReadMcpResource {
+    pub fn new(servers: McpServers) -> Self {
+        Self { servers }
+    }
+}
+
+#[async_trait]
+impl WrappedTool for ReadMcpResource {
+    async fn call(&self, input: Value) -> Result<String, ToolError> {
+        let input: ReadMcpResourceInput = parse_input(input)?;
+        find_server(&self.servers, &input.server)
+            .await?
+            .read_resource(&input.uri)
+            .await
+    }
+
+    fn to_tool(&self) -> AITool {
+        AITool {
+            name: "ReadMcpResource".to_string(),
+            description: Some(include_str!("./read_resource.md").to_string()),
+            schema: Some(schema_for!(ReadMcpResourceInput).to_value()),
+            config: None,
+        }
+    }
+
+    fn requires_permission(
+        &self,
+        _session: &Session,
+        _input: &Value,
+    ) -> Result<Permission, ToolError> {
+        Ok(Permission::Implicit)
+    }
+
+    fn describe_action(&self, input: &Value) -> String {
+        match serde_json::from_value::<ReadMcpResourceInput>(input.clone()) {
+            Ok(input) => format!("ReadMcpResource({}:{})", input.server, input.uri),
+            Err(_) => "ReadMcpResource".to_string(),
+        }
+    }
+
+    async fn generate_preview(&self, _input: &Value) -> Option<ToolPreview> {
+        None
+    }
+}
+
+/// The `@server:uri` mentions in `text` whose server is known, as `(server, uri)`.
+fn find_mentions<'a>(servers: &McpServers, text: &'a str) -> Vec<(&'a str, &'a str)> {
+    text.split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|mention| mention.trim_end_matches(['.', ',', ';', '!', '?', ')']))
+        .filter_map(|mention| mention.split_once(':'))
+        .filter(|(server, uri)| !uri.is_empty() && servers.server(server).is_some())
+        .collect()
+}
+
+/// Appends the contents of the resources mentioned as `@server:uri` in a
+/// prompt. Returns the prompt and the mentions that could not be read.
+pub async fn expand_resource_mentions(servers: &McpServers, text: &str) -> (String, Vec<String>) {
+    let mut prompt = text.to_string();
+    let mut errors = vec![];
+    let mut seen = vec![];
+    for (server, uri) in find_mentions(servers, text) {
+        if seen.contains(&(server, uri)) {
+            continue;
+        }
+        seen.push((server, uri));
+        let result = match find_server(servers, server).await {
+            Ok(client) => client.read_resource(uri).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(contents) => prompt.push_str(&format!(
+                "\n\n<resource server=\"{server}\" uri=\"{uri}\">\n{contents}\n</resource>"
+            )),
+            Err(err) => errors.push(format!("@{server}:{uri}: {err}")),
+        }
+    }
+    (prompt, errors)
+}
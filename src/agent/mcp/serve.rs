@@ -0,0 +1,201 @@
+//! `enchant mcp serve`: the built-in tools, offered to other MCP clients over stdio.
+
+use std::sync::Arc;
+
+use rmcp::{
+    ErrorData, RoleServer, ServerHandler, ServiceExt,
+    model::{
+        CallToolRequestParam, CallToolResult, Content, Implementation, ListToolsResult,
+        PaginatedRequestParam, ServerCapabilities, ServerInfo, Tool,
+    },
+    service::RequestContext,
+    transport::stdio,
+};
+use serde_json::Value;
+
+use crate::{
+    agent::{
+        DEFAULT_MODEL, Session, Toolset,
+        config::{Config, load_config},
+        tools::{builtin_tools, permission::Permission, tool_error::ToolError},
+    },
+    error::Error,
+    util::env::References,
+};
+
+/// Serves the tools of `session`. Calls are checked against the session's
+/// permissions: since nobody can approve them, only calls that need no
+/// approval are run.
+#[derive(Clone)]
+pub struct ToolServer {
+    session: Arc<Session>,
+}
+
+impl ToolServer {
+    pub fn new(session: Session) -> Self {
+        Self {
+            session: Arc::new(session),
+        }
+    }
+}
+
+impl ServerHandler for ToolServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: Implementation {
+                name: "enchant".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Implementation::default()
+            },
+            ..ServerInfo::default()
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        let tools = self
+            .session
+            .tools
+            .list_tools()
+            .into_iter()
+            .map(|tool| {
+                let schema = match tool.schema {
+                    Some(Value::Object(mut schema)) => {
+                        // MCP clients expect a plain object schema.
+                        schema.remove("$schema");
+                        schema.remove("title");
+                        schema
+                    }
+                    _ => serde_json::Map::from_iter([("type".to_string(), "object".into())]),
+                };
+                Tool::new(tool.name, tool.description.unwrap_or_default(), schema)
+            })
+            .collect();
+        Ok(ListToolsResult::with_all_items(tools))
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let tools = &self.session.tools;
+        let name = request.name.to_string();
+        let input = Value::Object(request.arguments.unwrap_or_default());
+
+        let permission = match tools.requires_permission(&self.session, &name, &input) {
+            Ok(permission) => permission,
+            Err(ToolError::ToolNotFound) => {
+                return Err(ErrorData::invalid_params(
+                    format!("Unknown tool '{name}'"),
+                    None,
+                ));
+            }
+            Err(err) => return Err(ErrorData::invalid_params(err.to_string(), None)),
+        };
+        let denied = match permission {
+            Permission::Implicit => None,
+            Permission::Never => Some("is not allowed"),
+            Permission::AllowAutomatic | Permission::RequireApproval => {
+                Some("requires approval, which can't be given over MCP")
+            }
+        };
+        if let Some(reason) = denied {
+            let action = tools.describe_action(&name, &input);
+            return Ok(CallToolResult::error(vec![Content::text(format!(
+                "{action} {reason}"
+            ))]));
+        }
+
+        Ok(match tools.call(name, input).await {
+            Ok(output) => CallToolResult::success(vec![Content::text(output)]),
+            Err(err) => CallToolResult::error(vec![Content::text(err.to_string())]),
+        })
+    }
+}
+
+/// Runs `enchant mcp serve` until the client disconnects. `overrides` are
+/// the settings given on the command line.
+pub async fn serve_stdio(overrides: Config) -> Result<(), Error> {
+    let mut config_state = load_config(References::Resolve).await?;
+    config_state.overrides = overrides;
+    let working_directory = std::env::current_dir()?;
+    let (config, errors) = config_state.session_config(&working_directory).await;
+    // Stdout belongs to the protocol; clients usually log stderr.
+    for warning in config_state
+        .warnings
+        .iter()
+        .cloned()
+        .chain(errors.iter().map(|e| format!("{e}\nThe file is ignored.")))
+    {
+        eprintln!("Warning: {warning}");
+    }
+    let model = config
+        .default_model
+        .clone()
+        .unwrap_or(DEFAULT_MODEL.to_string());
+    let session = Session::new_subagent(
+        config,
+        config_state.api_keys,
+        model,
+        Toolset::new(builtin_tools()),
+        String::new(),
+    )?;
+
+    let service = ToolServer::new(session)
+        .serve(stdio())
+        .await
+        .map_err(|e| Error::IOError(std::io::Error::other(e)))?;
+    service
+        .waiting()
+        .await
+        .map_err(|e| Error::IOError(std::io::Error::other(e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::agent::mcp::McpClient;
+
+    #[tokio::test]
+    async fn test_serve() {
+        let session = Session::new_subagent(
+            Config::default(),
+            Default::default(),
+            DEFAULT_MODEL.to_string(),
+            Toolset::new(builtin_tools()),
+            String::new(),
+        )
+        .unwrap();
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            let server = ToolServer::new(session).serve(server_io).await.unwrap();
+            server.waiting().await.unwrap();
+        });
+        let client = McpClient::connect("enchant", client_io).await.unwrap();
+
+        let tools = client.list_tools().await.unwrap();
+        let read = tools.iter().find(|t| t.name == "Read").unwrap();
+        assert_eq!(read.input_schema["type"], "object");
+        assert!(read.input_schema.get("$schema").is_none());
+
+        let output = client
+            .call_tool("Read", json!({ "path": "Cargo.toml" }))
+            .await
+            .unwrap();
+        assert!(output.content.contains("[package]"));
+
+        let err = client
+            .call_tool("Bash", json!({ "command": "touch /tmp/x" }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("requires approval"));
+    }
+}
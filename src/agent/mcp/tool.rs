@@ -3,30 +3,43 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use genai::chat::Tool as AITool;
 use serde_json::Value;
-use tokio::sync::Mutex;
 
 use crate::agent::{
     Session,
     tools::{
         permission::Permission,
-        tool::{ToolPreview, WrappedTool},
+        tool::{ToolOutput, ToolPreview, WrappedTool},
         tool_error::ToolError,
     },
 };
 
-use super::{McpClient, McpServerConfig, McpToolDef};
+use super::{McpServer, McpToolDef};
+
+/// Arguments longer than this are cut in the transcript.
+const MAX_ARGUMENT_CHARS: usize = 40;
 
 fn tool_fq_name(server_name: &str, tool_name: &str) -> String {
     format!("mcp.{}.{}", server_name, tool_name)
 }
 
-fn parse_permission(s: Option<&str>) -> Permission {
-    match s.unwrap_or("require_approval") {
-        "implicit" => Permission::Implicit,
-        "allow_automatic" => Permission::AllowAutomatic,
-        "require_approval" => Permission::RequireApproval,
-        "never" => Permission::Never,
-        _ => Permission::RequireApproval,
+/// The arguments of a call on one line, e.g. `query: "bug", limit: 5`.
+fn summarize_arguments(input: &Value) -> String {
+    let shorten = |value: &Value| {
+        let text = value.to_string();
+        if text.chars().count() <= MAX_ARGUMENT_CHARS {
+            return text;
+        }
+        let cut: String = text.chars().take(MAX_ARGUMENT_CHARS - 1).collect();
+        format!("{cut}…")
+    };
+    match input {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| format!("{key}: {}", shorten(value)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Value::Null => String::new(),
+        other => shorten(other),
     }
 }
 
@@ -34,27 +47,25 @@ fn parse_permission(s: Option<&str>) -> Permission {
 pub struct McpTool {
     pub server_name: String,
     pub tool_name: String,
+    pub title: Option<String>,
     pub description: Option<String>,
     pub input_schema: Value,
 
     permission: Permission,
-    client: Arc<Mutex<McpClient>>,
+    server: Arc<McpServer>,
 }
 
 impl McpTool {
-    pub fn new(
-        def: McpToolDef,
-        server_cfg: &McpServerConfig,
-        client: Arc<Mutex<McpClient>>,
-    ) -> Self {
-        let permission = parse_permission(server_cfg.permission.as_deref());
+    pub fn new(def: McpToolDef, server: Arc<McpServer>) -> Self {
+        let permission = server.config.tool_permission(&def);
         Self {
             server_name: def.server,
             tool_name: def.name,
+            title: def.title,
             description: def.description,
             input_schema: def.input_schema,
             permission,
-            client,
+            server,
         }
     }
 
@@ -66,9 +77,15 @@ impl McpTool {
 #[async_trait]
 impl WrappedTool for McpTool {
     async fn call(&self, input: Value) -> Result<String, ToolError> {
-        // MCP expects "arguments" object; we pass input as-is.
-        let mut client = self.client.lock().await;
-        client.call_tool(&self.tool_name, input).await
+        Ok(self.call_with_output(input).await?.content)
+    }
+
+    async fn call_with_output(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        self.server
+            .live_client()
+            .await?
+            .call_tool(&self.tool_name, input)
+            .await
     }
 
     fn to_tool(&self) -> AITool {
@@ -88,8 +105,18 @@ impl WrappedTool for McpTool {
         Ok(self.permission.clone())
     }
 
+    fn may_need_approval(&self) -> bool {
+        self.permission != Permission::Implicit
+    }
+
+    /// e.g. `github: Search issues(query: "bug")`, using the tool's title if it has one.
     fn describe_action(&self, input: &Value) -> String {
-        format!("{}({})", self.fq_name(), input)
+        let name = self.title.as_deref().unwrap_or(&self.tool_name);
+        format!(
+            "{}: {name}({})",
+            self.server_name,
+            summarize_arguments(input)
+        )
     }
 
     async fn generate_preview(&self, _input: &Value) -> Option<ToolPreview> {
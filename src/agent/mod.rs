@@ -1,38 +1,90 @@
+pub mod agents;
+pub mod budget;
+pub mod cache;
 pub mod config;
+pub mod history;
 pub mod mcp;
 pub mod models;
+pub mod notice;
 pub mod prompt;
+pub mod providers;
+pub mod reasoning;
+pub mod retry;
+pub mod thinking;
 pub mod tools;
+pub mod usage;
 
-use std::{path::PathBuf, sync::Arc};
-
-use tokio::fs;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
     agent::{
-        config::{Config, ConfigState, ProviderKey, ProviderKeys},
+        agents::load_agent_definitions,
+        budget::{BudgetLimit, BudgetUsage},
+        cache::with_cache_breakpoints,
+        config::{Config, ConfigState, ProviderKeys},
+        history::{SavedSession, new_session_id},
+        mcp::{
+            McpServer, McpServerStatus, McpServers, McpToolDef,
+            load::{mcp_tools, resource_tools},
+        },
+        models::model_info::{ModelInfo, ModelRegistry},
+        notice::{Notice, NoticeLevel},
         prompt::build_system_prompt,
+        reasoning::Reasoning,
+        retry::retry_delay,
+        thinking::{ThinkingBlocks, thinking_blocks, thinking_budget},
         tools::{
-            bash::Bash,
-            edit::Edit,
-            glob::Glob,
-            grep::Grep,
-            ls::Ls,
+            builtin_tools,
             permission::Permission,
-            read::Read,
+            task::Task,
             tool::{PermissionRequest, Toolset},
-            write::Write,
+            tool_error::ToolError,
         },
+        usage::{TokenUsage, UsageRecord, UsageTracker, append_ledger},
     },
+    commands::{CommandRegistry, custom::load_custom_commands, mcp_prompt::load_mcp_prompts},
     error::Error,
+    util::env::redact,
 };
 use genai::{
-    Client, ModelIden,
+    Client,
     adapter::AdapterKind,
-    chat::{ChatMessage, ChatRequest, ToolCall, ToolResponse},
-    resolver::{AuthData, AuthResolver},
+    chat::{
+        ChatMessage, ChatOptions, ChatRequest, ChatResponse, ChatRole, ContentPart,
+        ReasoningEffort, Tool, ToolCall, ToolResponse, Usage,
+    },
 };
 
+/// The model used when none is configured.
+pub const DEFAULT_MODEL: &str = "claude-haiku-4-5";
+
+/// Share of the context window (less room for the response) at which the
+/// conversation is compacted automatically.
+const AUTO_COMPACT_RATIO: f64 = 0.9;
+
+/// How long a fallback model is used before the primary one is tried again.
+const FALLBACK_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Per-turn overrides set by custom commands, cleared when the turn is done.
+#[derive(Clone, Debug, Default)]
+pub struct TurnOverrides {
+    pub model: Option<String>,
+    /// Only these tools are offered to the model.
+    pub tools: Option<Vec<String>>,
+}
+
+/// A fallback model standing in for the primary one.
+#[derive(Clone, Debug)]
+pub struct Fallback {
+    pub model: String,
+    pub since: Instant,
+}
+
 /// Represents a pending tool call that needs permission or execution.
 #[derive(Clone)]
 pub struct PendingToolCall {
@@ -49,15 +101,24 @@ pub enum ThinkResult {
     NeedsPermission(Vec<PermissionRequest>),
     /// The agent made progress (executed tools) and may need to continue.
     Continue,
+    /// A budget limit was reached. Call `extend_budget` to continue anyway.
+    BudgetReached(BudgetLimit),
 }
 
 #[derive(Clone)]
 pub struct Session {
+    /// Identifies the session in `~/.enchant/sessions`.
+    pub id: String,
     pub model: String,
     pub working_directory: PathBuf,
 
     pub messages: Vec<ChatMessage>,
+    /// Client-side notices interleaved with the messages in the transcript.
+    pub notices: Vec<Notice>,
     pub tools: Arc<Toolset>,
+    pub commands: Arc<CommandRegistry>,
+    /// The connected MCP servers.
+    pub mcp: McpServers,
     /// Pending tool calls from the last response, waiting to be executed.
     pending_calls: Vec<PendingToolCall>,
     /// Permission requests that have been approved (call_id -> approved).
@@ -66,8 +127,31 @@ pub struct Session {
     denied_calls: Vec<String>,
     /// Total tokens used in the current conversation.
     pub total_tokens: Option<i32>,
+    /// Tokens and cost of every model call in this session, its sub-agents' included.
+    pub usage: UsageTracker,
+    /// Whether model calls are appended to the usage ledger. Sub-agents leave
+    /// it to the session that started them.
+    ledger: bool,
+    /// Usage counted against `config.budget`.
+    pub budget_usage: BudgetUsage,
+    /// Nested activity reported by tool calls (call_id -> activity lines).
+    pub tool_activity: HashMap<String, Vec<String>>,
+    /// Errors of failed tool calls (call_id -> error), shown under the call.
+    pub tool_errors: HashMap<String, String>,
+    /// Overrides for the current turn.
+    pub overrides: TurnOverrides,
+    /// Reasoning effort set with `/think`, over the model's default.
+    pub reasoning_effort: Option<Reasoning>,
+    /// Reasoning of assistant messages (message index -> reasoning text).
+    pub reasoning: HashMap<usize, String>,
+    /// Signed thinking blocks of assistant messages, sent back to Anthropic
+    /// while the tools they called are answered.
+    pub thinking: ThinkingBlocks,
+    /// The fallback model in use while the primary one is unavailable.
+    pub fallback: Option<Fallback>,
 
     config: Config,
+    models: ModelRegistry,
     api_keys: ProviderKeys,
 }
 
@@ -87,53 +171,168 @@ impl Session {
             messages.push(ChatMessage::system(enchant_md));
         }
 
-        // // Load optional per-project enchant.json from working directory
-        // // (falls back to defaults if missing or unreadable)
-        let config: Config = match fs::read_to_string(working_directory.join("enchant.json")).await
-        {
-            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-            Err(_) => Config::default(),
-        };
-        let config = config_state.base.clone().merge(config);
+        // Shown to the user as notices once the session exists.
+        let mut warnings = config_state.warnings.clone();
+        let (config, errors) = config_state.session_config(&working_directory).await;
+        for err in errors {
+            warnings.push(format!("{err}\nThe file is ignored."));
+        }
 
         // Build tool list (built-ins + optional MCP)
-        let mut tools: Vec<Box<dyn crate::agent::tools::tool::WrappedTool + Send + Sync + 'static>> = vec![
-            Box::new(Read),
-            Box::new(Glob),
-            Box::new(Grep),
-            Box::new(Ls),
-            Box::new(Edit),
-            Box::new(Write),
-            Box::new(Bash),
-        ];
-
-        if !config.mcp_servers.is_empty() {
-            match crate::agent::mcp::load::load_mcp_tools(&config.mcp_servers).await {
-                Ok(mut mcp_tools) => tools.append(&mut mcp_tools),
-                Err(e) => {
-                    // Non-fatal: keep app usable even if MCP tools fail.
-                    messages.push(ChatMessage::system(format!(
-                        "Warning: failed to load MCP tools: {e}"
-                    )));
+        let mut tools = builtin_tools();
+
+        let (mcp, mut mcp_tools) =
+            crate::agent::mcp::load::load_mcp_tools(&config.mcp_servers).await;
+        tools.append(&mut mcp_tools);
+        for server in mcp.servers() {
+            // Non-fatal: the server can be reconnected from `/mcp`.
+            match server.status() {
+                McpServerStatus::Failed(reason) => warnings.push(format!(
+                    "MCP server '{}' failed to start: {reason}",
+                    server.name()
+                )),
+                McpServerStatus::Connected(client) => {
+                    warnings.extend(client.warning().map(str::to_string))
                 }
+                McpServerStatus::Disabled => {}
+            }
+        }
+
+        let (agents, agent_warnings) = load_agent_definitions(&working_directory).await;
+        warnings.extend(agent_warnings);
+
+        // Sub-agents pick their tools from everything except `Task` itself,
+        // leaving out the tools that may need approval: nobody can give it.
+        let tools = Toolset::new(tools);
+        let subagent_tools = agents
+            .iter()
+            .map(|a| (format!("Agent '{}'", a.name), a.tools.clone()))
+            .chain([("Config `subagent.tools`".to_string(), config.subagent.tools.clone())]);
+        for (owner, names) in subagent_tools {
+            if let Some(names) = names {
+                warnings.extend(unknown_tools_warning(&owner, &tools.subset(&names).1));
+                let gated: Vec<_> = names
+                    .iter()
+                    .filter(|name| tools.may_need_approval(name))
+                    .cloned()
+                    .collect();
+                if !gated.is_empty() {
+                    warnings.push(format!(
+                        "{owner} can't use tools that need approval: {}",
+                        gated.join(", ")
+                    ));
+                }
+            }
+        }
+        let implicit: Vec<_> = tools
+            .list_tools()
+            .into_iter()
+            .map(|t| t.name)
+            .filter(|name| !tools.may_need_approval(name))
+            .collect();
+        let task = Task::new(
+            Arc::new(tools.subset(&implicit).0),
+            agents,
+            config.clone(),
+            config_state.api_keys.clone(),
+        );
+        let tools = tools.with_tool(Box::new(task));
+
+        let mut commands = CommandRegistry::builtin();
+        let (custom_commands, command_warnings) = load_custom_commands(&working_directory).await;
+        warnings.extend(command_warnings);
+        for command in custom_commands {
+            if let Some(names) = &command.tools {
+                let owner = format!("Custom command /{}", command.name);
+                warnings.extend(unknown_tools_warning(&owner, &tools.subset(names).1));
             }
+            if commands.get(&command.name).is_some() {
+                warnings.push(format!(
+                    "Custom command /{} is shadowed by a built-in command",
+                    command.name
+                ));
+                continue;
+            }
+            commands = commands.with_command(Arc::new(command));
+        }
+        let (prompt_commands, prompt_warnings) = load_mcp_prompts(&mcp).await;
+        warnings.extend(prompt_warnings);
+        for command in prompt_commands {
+            commands = commands.with_command(Arc::new(command));
         }
 
-        Self {
-            model: config
-                .default_model
-                .clone()
-                .unwrap_or("claude-haiku-4-5".to_string()),
+        let models = ModelRegistry::builtin().with_models(&config.models);
+        let model = config.default_model.as_deref().unwrap_or(DEFAULT_MODEL);
+
+        let mut session = Self {
+            id: new_session_id(),
+            model: models.resolve(model),
             working_directory,
             messages,
-            tools: Arc::new(Toolset::new(tools)),
+            notices: vec![],
+            tools: Arc::new(tools),
+            commands: Arc::new(commands),
+            mcp,
             pending_calls: vec![],
             approved_calls: vec![],
             denied_calls: vec![],
             total_tokens: None,
+            usage: UsageTracker::default(),
+            ledger: true,
+            budget_usage: BudgetUsage::default(),
+            tool_activity: HashMap::new(),
+            tool_errors: HashMap::new(),
+            overrides: TurnOverrides::default(),
+            reasoning_effort: None,
+            reasoning: HashMap::new(),
+            thinking: ThinkingBlocks::new(),
+            fallback: None,
             config: config.clone(),
+            models,
             api_keys: config_state.api_keys.clone(),
+        };
+        for warning in warnings {
+            session.notify(NoticeLevel::Warning, warning);
         }
+        session
+    }
+
+    /// Creates a session for a sub-agent, with its own history and toolset.
+    pub fn new_subagent(
+        config: Config,
+        api_keys: ProviderKeys,
+        model: String,
+        tools: Toolset,
+        system_prompt: String,
+    ) -> Result<Self, Error> {
+        let models = ModelRegistry::builtin().with_models(&config.models);
+        Ok(Self {
+            id: new_session_id(),
+            model: models.resolve(&model),
+            working_directory: std::env::current_dir()?,
+            messages: vec![ChatMessage::system(system_prompt)],
+            notices: vec![],
+            tools: Arc::new(tools),
+            commands: Arc::new(CommandRegistry::default()),
+            mcp: McpServers::default(),
+            pending_calls: vec![],
+            approved_calls: vec![],
+            denied_calls: vec![],
+            total_tokens: None,
+            usage: UsageTracker::default(),
+            ledger: false,
+            budget_usage: BudgetUsage::default(),
+            tool_activity: HashMap::new(),
+            tool_errors: HashMap::new(),
+            overrides: TurnOverrides::default(),
+            reasoning_effort: None,
+            reasoning: HashMap::new(),
+            thinking: ThinkingBlocks::new(),
+            fallback: None,
+            config,
+            models,
+            api_keys,
+        })
     }
 
     /// Perform one step of thinking. Returns whether we're done or need permission.
@@ -143,14 +342,34 @@ impl Session {
             return self.process_pending_calls().await;
         }
 
-        // Otherwise, get a new response from the model
-        let client = Client::builder()
-            .with_auth_resolver(auth_resolver(&self.api_keys))
-            .build();
+        if let Some(limit) = self.budget_usage.exceeded(&self.config.budget) {
+            return Ok(ThinkResult::BudgetReached(limit));
+        }
 
-        let request = ChatRequest::new(self.messages.clone()).with_tools(self.tools.list_tools());
+        // Otherwise, get a new response from the model
+        self.sync_mcp_tools();
+        let tools = match &self.overrides.tools {
+            Some(names) => self.tools.subset(names).0.list_tools(),
+            None => self.tools.list_tools(),
+        };
+        let model = match &self.overrides.model {
+            Some(model) => self.models.resolve(model),
+            None => self.current_model(),
+        };
 
-        let response = client.exec_chat(&self.model, request, None).await?;
+        let (model, mut response) = self.exec_chat(model, None, tools).await?;
+        self.record_usage(&model, &response.usage).await;
+        if let Some(reasoning) = response.reasoning_content.take().filter(|r| !r.trim().is_empty()) {
+            self.reasoning.insert(self.messages.len(), reasoning);
+        }
+        let blocks = response
+            .captured_raw_body
+            .as_ref()
+            .map(thinking_blocks)
+            .unwrap_or_default();
+        if !blocks.is_empty() {
+            self.thinking.insert(self.messages.len(), blocks);
+        }
 
         // Update total tokens from response usage
         if let Some(total) = response.usage.total_tokens {
@@ -163,6 +382,7 @@ impl Session {
         // If no tool calls, we're done - add the final response and exit
         if tool_calls.is_empty() {
             self.messages.push(ChatMessage::assistant(response.content));
+            self.overrides = TurnOverrides::default();
             return Ok(ThinkResult::Done);
         }
 
@@ -170,10 +390,13 @@ impl Session {
         self.pending_calls = tool_calls
             .iter()
             .map(|call| PendingToolCall {
-                permission_requirement: self
-                    .tools
-                    .requires_permission(self, &call.fn_name, &call.fn_arguments)
-                    .unwrap_or(Permission::RequireApproval),
+                permission_requirement: if self.is_tool_allowed(&call.fn_name) {
+                    self.tools
+                        .requires_permission(self, &call.fn_name, &call.fn_arguments)
+                        .unwrap_or(Permission::RequireApproval)
+                } else {
+                    Permission::Never
+                },
                 call: (*call).clone(),
             })
             .collect();
@@ -185,6 +408,121 @@ impl Session {
         self.process_pending_calls().await
     }
 
+    /// Sends the conversation to `model`, followed by `prompt` if given, moving
+    /// down `fallback_models` while the model tried is overloaded or
+    /// unavailable. Returns the model that answered.
+    async fn exec_chat(
+        &mut self,
+        model: String,
+        prompt: Option<ChatMessage>,
+        tools: Vec<Tool>,
+    ) -> Result<(String, ChatResponse), Error> {
+        let client = self.client();
+        let fallbacks: Vec<String> = self
+            .config
+            .fallback_models
+            .iter()
+            .map(|m| self.models.resolve(m))
+            .collect();
+        // Starting from a fallback, only the ones after it are left to try.
+        let rest = match fallbacks.iter().position(|m| *m == model) {
+            Some(index) => fallbacks[index + 1..].to_vec(),
+            None => fallbacks,
+        };
+
+        let tool_loop = self.tool_loop_start();
+        let mut first_error = None;
+        for candidate in std::iter::once(model.clone()).chain(rest) {
+            let mut messages = with_cache_breakpoints(self.messages.clone(), &candidate);
+            messages.extend(prompt.clone());
+            let anthropic =
+                AdapterKind::from_model(&candidate).ok() == Some(AdapterKind::Anthropic);
+            let effort = self.reasoning_for(&candidate, tool_loop);
+
+            let result = match effort.as_ref().and_then(thinking_budget) {
+                // Only these requests replay thinking blocks, which genai can't send.
+                Some(budget) if anthropic && tool_loop.is_some() => {
+                    match client.resolve_service_target(&candidate).await {
+                        Ok(target) => {
+                            thinking::exec_chat(target, &messages, &self.thinking, &tools, budget)
+                                .await
+                        }
+                        Err(err) => Err(Error::from(err)),
+                    }
+                }
+                _ => {
+                    let request = ChatRequest::new(messages).with_tools(tools.clone());
+                    let options = match effort {
+                        // The raw body holds Anthropic's signed thinking blocks.
+                        Some(effort) => ChatOptions::default()
+                            .with_reasoning_effort(effort)
+                            .with_capture_raw_body(anthropic),
+                        None => ChatOptions::default(),
+                    };
+                    client
+                        .exec_chat(&candidate, request, Some(&options))
+                        .await
+                        .map_err(Error::from)
+                }
+            };
+            let err = match result {
+                Ok(response) => {
+                    if candidate != model {
+                        self.switch_to_fallback(&model, &candidate, first_error.as_ref());
+                    }
+                    return Ok((candidate, response));
+                }
+                Err(err) => err,
+            };
+            if !err.should_fall_back() {
+                return Err(err);
+            }
+            first_error.get_or_insert(err);
+        }
+        Err(first_error.expect("at least one model was tried"))
+    }
+
+    fn switch_to_fallback(&mut self, model: &str, fallback: &str, err: Option<&Error>) {
+        let reason = err
+            .map(|e| e.to_string().lines().next().unwrap_or_default().to_string())
+            .unwrap_or_default();
+        self.notify(
+            NoticeLevel::Warning,
+            format!(
+                "{} failed ({reason}), switched to {}",
+                self.display_name(model),
+                self.display_name(fallback)
+            ),
+        );
+        // Per-turn models of custom commands are not replaced for later turns.
+        if self.overrides.model.is_none() {
+            self.fallback = Some(Fallback {
+                model: fallback.to_string(),
+                since: Instant::now(),
+            });
+        }
+    }
+
+    /// The model to use: the primary one, or the fallback standing in for it
+    /// until the cooldown is over.
+    fn current_model(&mut self) -> String {
+        if let Some(fallback) = &self.fallback {
+            if fallback.since.elapsed() < FALLBACK_COOLDOWN {
+                return fallback.model.clone();
+            }
+            self.fallback = None;
+            self.notify(
+                NoticeLevel::Info,
+                format!("Switched back to {}", self.display_name(&self.model)),
+            );
+        }
+        self.model.clone()
+    }
+
+    fn display_name(&self, model: &str) -> String {
+        self.models.get(model).name.unwrap_or(model.to_string())
+    }
+
     /// Process pending tool calls, checking for permission requirements.
     async fn process_pending_calls(&mut self) -> Result<ThinkResult, Error> {
         // Check if any calls need permission and haven't been approved/denied yet
@@ -225,9 +563,16 @@ impl Session {
 
         // All permissions resolved, execute the calls
         let mut tool_responses = vec![];
+        let mut images = vec![];
+        let mut tool_usage = vec![];
 
         for pending in &self.pending_calls {
-            let response = if self.denied_calls.contains(&pending.call.call_id) {
+            let response = if pending.permission_requirement == Permission::Never {
+                ToolResponse::new(
+                    pending.call.call_id.clone(),
+                    "Error: This tool is not allowed".to_string(),
+                )
+            } else if self.denied_calls.contains(&pending.call.call_id) {
                 // Permission denied
                 ToolResponse::new(
                     pending.call.call_id.clone(),
@@ -235,23 +580,52 @@ impl Session {
                 )
             } else {
                 // Execute the tool
+                self.budget_usage.tool_calls += 1;
+                let budget = self.config.budget.remaining(&self.budget_usage);
                 match self
                     .tools
-                    .call(
-                        pending.call.fn_name.clone(),
+                    .call_with_budget(
+                        &pending.call.fn_name,
                         pending.call.fn_arguments.clone(),
+                        budget,
                     )
                     .await
                 {
-                    Ok(resp) => ToolResponse::new(pending.call.call_id.clone(), resp),
+                    Ok(output) => {
+                        self.budget_usage.tool_calls += output.tool_calls;
+                        tool_usage.extend(output.usage);
+                        if !output.activity.is_empty() {
+                            self.tool_activity
+                                .insert(pending.call.call_id.clone(), output.activity);
+                        }
+                        if !output.images.is_empty() {
+                            images.push((pending.call.fn_name.clone(), output.images));
+                        }
+                        if output.failed {
+                            self.tool_errors
+                                .insert(pending.call.call_id.clone(), output.content.clone());
+                            let content = format!("Error: {}", output.content);
+                            ToolResponse::new(pending.call.call_id.clone(), content)
+                        } else {
+                            ToolResponse::new(pending.call.call_id.clone(), output.content)
+                        }
+                    }
                     Err(e) => {
-                        ToolResponse::new(pending.call.call_id.clone(), format!("Error: {}", e))
+                        let message = match e {
+                            ToolError::Error { message } => message,
+                            other => other.to_string(),
+                        };
+                        self.tool_errors
+                            .insert(pending.call.call_id.clone(), message.clone());
+                        ToolResponse::new(pending.call.call_id.clone(), format!("Error: {message}"))
                     }
                 }
             };
             tool_responses.push(response);
         }
 
+        self.record_tool_usage(tool_usage).await;
+
         // Clear pending state
         self.pending_calls.clear();
         self.approved_calls.clear();
@@ -261,6 +635,12 @@ impl Session {
         for tool_response in tool_responses {
             self.messages.push(ChatMessage::from(tool_response));
         }
+        // Tool results can only be text, so images follow in a user message.
+        for (name, binaries) in images {
+            let mut parts = vec![ContentPart::from_text(format!("Images returned by {name}:"))];
+            parts.extend(binaries.into_iter().map(ContentPart::Binary));
+            self.messages.push(ChatMessage::user(parts));
+        }
 
         Ok(ThinkResult::Continue)
     }
@@ -291,31 +671,474 @@ impl Session {
 
     pub fn message(&mut self, message: String) -> Result<(), Error> {
         self.messages.push(ChatMessage::user(message));
+        self.usage.turn += 1;
+        self.budget_usage.turns = 0;
         Ok(())
     }
-}
 
-pub fn auth_resolver(keys: &ProviderKeys) -> AuthResolver {
-    let keys = keys.clone();
-    AuthResolver::from_resolver_fn(
-        move |model_iden: ModelIden| -> Result<Option<AuthData>, genai::resolver::Error> {
-            match model_iden.adapter_kind {
-                AdapterKind::OpenAI | AdapterKind::OpenAIResp => {
-                    if let ProviderKey::OpenAI { api_key } = keys.get("openai").unwrap() {
-                        Ok(Some(AuthData::from_single(api_key.clone())))
-                    } else {
-                        unimplemented!()
-                    }
-                }
-                AdapterKind::Anthropic => {
-                    if let ProviderKey::Anthropic { api_key } = keys.get("anthropic").unwrap() {
-                        Ok(Some(AuthData::from_single(api_key.clone())))
-                    } else {
-                        unimplemented!()
-                    }
+    /// Raises a budget limit that was reached, so the agent can run as long again.
+    pub fn extend_budget(&mut self, limit: &BudgetLimit) {
+        self.budget_usage.extend(&mut self.config.budget, limit);
+    }
+
+    /// Adds the usage of a model call to the session and the ledger.
+    async fn record_usage(&mut self, model: &str, usage: &Usage) {
+        let tokens = TokenUsage::from_usage(usage);
+        let cost = tokens.cost(self.models.get(model).pricing.as_ref());
+        self.budget_usage.turns += 1;
+        self.record_tokens(model, tokens, cost).await;
+    }
+
+    /// Adds the model calls a tool made, e.g. a sub-agent's, to the session
+    /// and the ledger. They don't count as turns of this session.
+    async fn record_tool_usage(&mut self, records: Vec<UsageRecord>) {
+        for record in records {
+            self.record_tokens(&record.model, record.tokens, record.cost)
+                .await;
+        }
+    }
+
+    async fn record_tokens(&mut self, model: &str, tokens: TokenUsage, cost: Option<f64>) {
+        self.budget_usage.tokens += tokens.total();
+        self.budget_usage.cost += cost.unwrap_or(0.0);
+        let record = self.usage.record(model, tokens, cost);
+        if !self.ledger {
+            return;
+        }
+        if let Err(err) = append_ledger(&self.id, &self.working_directory, record).await {
+            self.notify(
+                NoticeLevel::Warning,
+                format!("Failed to update the usage ledger: {err}"),
+            );
+        }
+    }
+
+    /// The reasoning effort for the current model: the one set with `/think`,
+    /// or the model's default.
+    pub fn current_reasoning(&self) -> Option<Reasoning> {
+        self.reasoning_effort.or(self.model_info().reasoning_effort)
+    }
+
+    /// Turns reasoning off, or back on at the model's default effort.
+    pub fn toggle_reasoning(&mut self) -> Reasoning {
+        let reasoning = match self.current_reasoning() {
+            Some(reasoning) if reasoning.is_on() => Reasoning::default(),
+            _ => self
+                .model_info()
+                .reasoning_effort
+                .filter(|r| r.is_on())
+                .unwrap_or(Reasoning::ON),
+        };
+        self.reasoning_effort = Some(reasoning);
+        reasoning
+    }
+
+    /// The reasoning option to send with a request to `model`, given the
+    /// start of the tool loop the request continues, if any.
+    fn reasoning_for(&self, model: &str, tool_loop: Option<usize>) -> Option<ReasoningEffort> {
+        let info = self.models.get(model);
+        if info.reasoning == Some(false) {
+            return None;
+        }
+        let reasoning = self.reasoning_effort.or(info.reasoning_effort)?;
+        // With thinking on, Anthropic continues a tool loop from the signed
+        // thinking block that started it. A loop that started without one, e.g.
+        // with reasoning off or on another provider, continues without thinking.
+        if let Some(start) = tool_loop
+            && AdapterKind::from_model(model).ok() == Some(AdapterKind::Anthropic)
+            && !self.thinking.contains_key(&start)
+        {
+            return None;
+        }
+        Some(reasoning.effort())
+    }
+
+    /// When the last assistant message called tools, the index of the
+    /// assistant message that started this run of tool calls, right after
+    /// the prompt.
+    fn tool_loop_start(&self) -> Option<usize> {
+        let last = self
+            .messages
+            .iter()
+            .rposition(|m| matches!(m.role, ChatRole::Assistant))?;
+        if !self.messages[last].content.contains_tool_call() {
+            return None;
+        }
+        let mut start = last;
+        for (index, message) in self.messages[..last].iter().enumerate().rev() {
+            match message.role {
+                ChatRole::Assistant => start = index,
+                ChatRole::Tool => {}
+                // Images returned by tools follow their results as a user message.
+                ChatRole::User
+                    if index > 0 && matches!(self.messages[index - 1].role, ChatRole::Tool) => {}
+                _ => break,
+            }
+        }
+        Some(start)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The built-in models merged with the configured ones.
+    pub fn models(&self) -> &ModelRegistry {
+        &self.models
+    }
+
+    pub fn model_info(&self) -> ModelInfo {
+        self.models.get(&self.model)
+    }
+
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// Replaces the tools of an MCP server with `defs`. Returns the number of tools.
+    ///
+    /// The resource tools are added with the first connected server that has
+    /// resources, if no server had them at startup.
+    pub fn replace_mcp_tools(&mut self, server: &Arc<McpServer>, defs: Vec<McpToolDef>) -> usize {
+        let prefix = format!("mcp.{}.", server.name());
+        let others: Vec<String> = self
+            .tools
+            .list_tools()
+            .into_iter()
+            .map(|t| t.name)
+            .filter(|name| !name.starts_with(&prefix))
+            .collect();
+        let mut tools = mcp_tools(server, defs);
+        let count = tools.len();
+        if server.client().is_ok_and(|c| c.supports_resources()) {
+            tools.extend(
+                resource_tools(&self.mcp)
+                    .into_iter()
+                    .filter(|tool| !others.contains(&tool.to_tool().name)),
+            );
+        }
+        let toolset = tools
+            .into_iter()
+            .fold(self.tools.subset(&others).0, |toolset, tool| {
+                toolset.with_tool(tool)
+            });
+        self.tools = Arc::new(toolset);
+        count
+    }
+
+    /// Picks up the tools of MCP servers that changed them or were restarted.
+    fn sync_mcp_tools(&mut self) {
+        let servers: Vec<_> = self.mcp.servers().cloned().collect();
+        for server in servers {
+            let updates = server.client().ok().and_then(|c| c.take_tool_updates());
+            if let Some(defs) = updates {
+                self.replace_mcp_tools(&server, defs);
+            }
+        }
+    }
+
+    /// Adds a notice to the transcript after the current messages.
+    pub fn notify(&mut self, level: NoticeLevel, text: impl Into<String>) {
+        self.notices.push(Notice {
+            position: self.messages.len(),
+            level,
+            text: redact(&text.into()),
+        });
+    }
+
+    /// Starts a new conversation, keeping the system prompt and project instructions.
+    pub fn clear(&mut self) {
+        self.messages = self.system_messages();
+        self.id = new_session_id();
+        self.reset_turn_state();
+    }
+
+    /// Replaces the conversation with a saved one.
+    pub fn restore(&mut self, saved: SavedSession) {
+        let mut messages = self.system_messages();
+        messages.extend(
+            saved
+                .messages
+                .into_iter()
+                .filter(|m| !matches!(m.role, ChatRole::System)),
+        );
+        self.messages = messages;
+        self.id = saved.id;
+        self.model = saved.model;
+        self.fallback = None;
+        self.reset_turn_state();
+    }
+
+    /// Replaces the conversation with a summary of it written by the model.
+    /// Like other requests, it is retried on transient errors and falls back
+    /// to `fallback_models`.
+    pub async fn compact(&mut self, instructions: Option<&str>) -> Result<(), Error> {
+        let mut prompt = include_str!("../../prompts/COMPACT.md").to_string();
+        if let Some(instructions) = instructions {
+            prompt.push_str(&format!("\nAdditional instructions: {instructions}\n"));
+        }
+
+        let model = self.current_model();
+        // The history is cached by the previous request, so only the prompt is new.
+        // Tools are declared since the history may reference them, but never run.
+        let tools = self.tools.list_tools();
+        let earlier_notices = self.notices.len();
+        let mut attempt = 0;
+        let (model, response) = loop {
+            let request = Some(ChatMessage::user(prompt.clone()));
+            match self.exec_chat(model.clone(), request, tools.clone()).await {
+                Ok(answer) => break answer,
+                Err(err) => {
+                    attempt += 1;
+                    let Some(delay) = retry_delay(&err, attempt) else {
+                        return Err(err);
+                    };
+                    tokio::time::sleep(delay).await;
                 }
-                _ => todo!(),
             }
-        },
-    )
+        };
+        self.record_usage(&model, &response.usage).await;
+        let summary = response.content.joined_texts().unwrap_or_default();
+
+        let mut messages = self.system_messages();
+        messages.push(ChatMessage::system(format!(
+            "Summary of the conversation so far:\n\n{summary}"
+        )));
+        self.messages = messages;
+        // Notices of the compaction itself, e.g. a switch to a fallback, stay.
+        let notices: Vec<_> = self.notices.drain(earlier_notices..).collect();
+        self.reset_turn_state();
+        for notice in notices {
+            self.notify(notice.level, notice.text);
+        }
+        Ok(())
+    }
+
+    /// Compacts the conversation once it fills most of the model's context window.
+    /// Returns whether it was compacted.
+    pub async fn compact_if_needed(&mut self) -> Result<bool, Error> {
+        let info = self.model_info();
+        let (Some(total), Some(max_context)) = (self.total_tokens, info.max_context) else {
+            return Ok(false);
+        };
+        let reserved = info.max_output.unwrap_or(0).min(max_context / 4);
+        if (total as f64) < (max_context - reserved) as f64 * AUTO_COMPACT_RATIO {
+            return Ok(false);
+        }
+
+        // Keep the overrides of a custom command that is about to run.
+        let overrides = self.overrides.clone();
+        self.compact(None).await?;
+        self.overrides = overrides;
+        Ok(true)
+    }
+
+    fn is_tool_allowed(&self, name: &str) -> bool {
+        match &self.overrides.tools {
+            Some(names) => names.iter().any(|n| n == name),
+            None => true,
+        }
+    }
+
+    /// The leading system messages (system prompt and project instructions).
+    fn system_messages(&self) -> Vec<ChatMessage> {
+        self.messages
+            .iter()
+            .take_while(|m| matches!(m.role, ChatRole::System))
+            .cloned()
+            .collect()
+    }
+
+    fn reset_turn_state(&mut self) {
+        self.overrides = TurnOverrides::default();
+        self.notices.clear();
+        self.reasoning.clear();
+        self.thinking.clear();
+        self.tool_activity.clear();
+        self.tool_errors.clear();
+        self.pending_calls.clear();
+        self.approved_calls.clear();
+        self.denied_calls.clear();
+        self.total_tokens = None;
+    }
+
+    fn client(&self) -> Client {
+        providers::client(&self.api_keys)
+    }
+}
+
+/// Warns about tool names in a tool list that match no tool.
+fn unknown_tools_warning(owner: &str, unknown: &[String]) -> Option<String> {
+    if unknown.is_empty() {
+        return None;
+    }
+    Some(format!("{owner} lists unknown tools: {}", unknown.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// A mock OpenAI-compatible provider answering one request with `status`.
+    pub(crate) fn mock_provider(status: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 64 * 1024];
+            let _ = stream.read(&mut request).unwrap();
+            let body = if status.starts_with("200") {
+                r#"{"id":"1","object":"chat.completion","model":"m","choices":[{"index":0,"message":{"role":"assistant","content":"pong"},"finish_reason":"stop"}],"usage":{"prompt_tokens":1,"completion_tokens":1,"total_tokens":2}}"#
+            } else {
+                r#"{"error":{"type":"overloaded_error","message":"Overloaded"}}"#
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        });
+        port
+    }
+
+    #[test]
+    fn test_fallback() {
+        let busy = mock_provider("529 Overloaded");
+        let spare = mock_provider("200 OK");
+        let keys: ProviderKeys = serde_json::from_value(serde_json::json!({
+            "busy": { "provider": "openai_compatible", "base_url": format!("http://127.0.0.1:{busy}/v1") },
+            "spare": { "provider": "openai_compatible", "base_url": format!("http://127.0.0.1:{spare}/v1") },
+        }))
+        .unwrap();
+        let config = Config {
+            fallback_models: vec!["spare::m".to_string()],
+            ..Default::default()
+        };
+        let mut session = Session::new_subagent(
+            config,
+            keys,
+            "busy::m".to_string(),
+            Toolset::new(vec![]),
+            "prompt".to_string(),
+        )
+        .unwrap();
+        session.message("ping".to_string()).unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (model, response) = runtime
+            .block_on(session.exec_chat("busy::m".to_string(), None, vec![]))
+            .unwrap();
+        assert_eq!(model, "spare::m");
+        assert_eq!(response.first_text(), Some("pong"));
+        assert_eq!(session.current_model(), "spare::m");
+        assert!(session.notices[0].text.contains("Provider unavailable: Overloaded"));
+    }
+
+    #[test]
+    fn test_thinking_across_tool_calls() {
+        let mut session = Session::new_subagent(
+            Config::default(),
+            Default::default(),
+            "claude-sonnet-4-5".to_string(),
+            Toolset::new(vec![]),
+            "prompt".to_string(),
+        )
+        .unwrap();
+        session.reasoning_effort = Some(Reasoning::ON);
+        let model = session.model.clone();
+        session.message("ping".to_string()).unwrap();
+        assert_eq!(session.tool_loop_start(), None);
+        assert!(session.reasoning_for(&model, None).is_some());
+
+        session.messages.push(ChatMessage::from(vec![ToolCall {
+            call_id: "call-1".to_string(),
+            fn_name: "Read".to_string(),
+            fn_arguments: serde_json::json!({}),
+        }]));
+        session
+            .messages
+            .push(ChatMessage::from(ToolResponse::new("call-1", "done")));
+        session
+            .messages
+            .push(ChatMessage::user("Images returned by Read:"));
+        assert_eq!(session.tool_loop_start(), Some(2));
+        // Without the signed block that started the loop, thinking stays off.
+        assert!(session.reasoning_for(&model, Some(2)).is_none());
+        assert!(session.reasoning_for("gpt-5.2", Some(2)).is_some());
+
+        let signed = serde_json::json!({ "type": "thinking", "thinking": "", "signature": "sig" });
+        session.thinking.insert(2, vec![signed]);
+        assert!(session.reasoning_for(&model, Some(2)).is_some());
+
+        session.messages.push(ChatMessage::assistant("pong"));
+        assert_eq!(session.tool_loop_start(), None);
+    }
+
+    #[tokio::test]
+    async fn test_compact_fallback() {
+        let busy = mock_provider("529 Overloaded");
+        let spare = mock_provider("200 OK");
+        let keys: ProviderKeys = serde_json::from_value(serde_json::json!({
+            "busy": { "provider": "openai_compatible", "base_url": format!("http://127.0.0.1:{busy}/v1") },
+            "spare": { "provider": "openai_compatible", "base_url": format!("http://127.0.0.1:{spare}/v1") },
+        }))
+        .unwrap();
+        let config = Config {
+            fallback_models: vec!["spare::m".to_string()],
+            ..Default::default()
+        };
+        let mut session = Session::new_subagent(
+            config,
+            keys,
+            "busy::m".to_string(),
+            Toolset::new(vec![]),
+            "prompt".to_string(),
+        )
+        .unwrap();
+        session.message("ping".to_string()).unwrap();
+
+        session.compact(None).await.unwrap();
+        let summary = session.messages.last().unwrap().content.first_text();
+        assert_eq!(summary, Some("Summary of the conversation so far:\n\npong"));
+        assert_eq!(session.current_model(), "spare::m");
+        assert_eq!(session.notices.len(), 1);
+        assert!(session.notices[0].text.contains("switched to"));
+    }
+
+    #[tokio::test]
+    async fn test_record_tool_usage() {
+        let mut session = Session::new_subagent(
+            Config::default(),
+            Default::default(),
+            "m".to_string(),
+            Toolset::new(vec![]),
+            "prompt".to_string(),
+        )
+        .unwrap();
+        session.message("ping".to_string()).unwrap();
+        let tokens = TokenUsage {
+            input: 10,
+            output: 5,
+            ..Default::default()
+        };
+        let record = UsageRecord {
+            turn: 7,
+            model: "sub".to_string(),
+            tokens,
+            cost: Some(0.5),
+        };
+        session.record_tool_usage(vec![record]).await;
+
+        assert_eq!(session.usage.records[0].turn, 1);
+        assert_eq!(session.usage.total_tokens(), tokens);
+        assert_eq!(session.usage.total_cost(), 0.5);
+        assert_eq!(session.budget_usage.tokens, 15);
+        assert_eq!(session.budget_usage.turns, 0);
+    }
 }
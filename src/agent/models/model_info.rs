@@ -1,45 +1,196 @@
 use std::{collections::HashMap, sync::OnceLock};
 
 use iocraft::Color;
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize, Default)]
+use crate::agent::reasoning::Reasoning;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct ModelInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Provider of the model, e.g. `anthropic`. Only used for display.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Context window, in tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_context: Option<i32>,
+    /// Maximum output tokens per response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
+    /// Whether the model supports extended thinking / reasoning effort.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<bool>,
+    /// Default reasoning effort (`low`, `medium`, `high`) or thinking budget in tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<Reasoning>,
+    /// Other names the model can be selected by, e.g. `sonnet`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+/// Prices in dollars per million tokens.
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+    /// Defaults to the input price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read: Option<f64>,
+    /// Defaults to the input price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
 }
 
 impl ModelInfo {
     pub fn get_color(&self) -> Color {
-        let name = if let Some(name) = &self.name {
-            name
-        } else {
-            return Color::White;
+        const ANTHROPIC: Color = Color::Rgb {
+            r: 204,
+            g: 124,
+            b: 94,
         };
-        match name {
-            name if name.starts_with("Claude") => Color::Rgb {
-                r: 204,
-                g: 124,
-                b: 94,
-            },
-            name if name.starts_with("GPT") || name.starts_with("OpenAI") => Color::Rgb {
-                r: 78,
-                g: 212,
-                b: 121,
-            },
+        const OPENAI: Color = Color::Rgb {
+            r: 78,
+            g: 212,
+            b: 121,
+        };
+        const GEMINI: Color = Color::Rgb {
+            r: 66,
+            g: 133,
+            b: 244,
+        };
+
+        match self.provider.as_deref() {
+            Some("anthropic") => return ANTHROPIC,
+            Some("openai") => return OPENAI,
+            Some("gemini") => return GEMINI,
+            _ => {}
+        }
+        match self.name.as_deref() {
+            Some(name) if name.starts_with("Claude") => ANTHROPIC,
+            Some(name) if name.starts_with("GPT") || name.starts_with("OpenAI") => OPENAI,
+            Some(name) if name.starts_with("Gemini") => GEMINI,
             _ => Color::White,
         }
     }
+
+    /// Overrides the fields set in `overlay`.
+    pub fn merge(self, overlay: Self) -> Self {
+        Self {
+            name: overlay.name.or(self.name),
+            provider: overlay.provider.or(self.provider),
+            max_context: overlay.max_context.or(self.max_context),
+            max_output: overlay.max_output.or(self.max_output),
+            pricing: overlay.pricing.or(self.pricing),
+            reasoning: overlay.reasoning.or(self.reasoning),
+            reasoning_effort: overlay.reasoning_effort.or(self.reasoning_effort),
+            aliases: if overlay.aliases.is_empty() {
+                self.aliases
+            } else {
+                overlay.aliases
+            },
+        }
+    }
 }
 
 pub type ModelInfoMap = HashMap<String, ModelInfo>;
 
+/// Merges `overlay` into `base`, field by field for models present in both.
+pub fn merge_models(mut base: ModelInfoMap, overlay: ModelInfoMap) -> ModelInfoMap {
+    for (id, info) in overlay {
+        let merged = base.remove(&id).unwrap_or_default().merge(info);
+        base.insert(id, merged);
+    }
+    base
+}
+
 static MODEL_INFO: OnceLock<ModelInfoMap> = OnceLock::new();
 
-pub fn get_model_info(name: &str) -> ModelInfo {
-    MODEL_INFO
-        .get_or_init(|| serde_json::from_str(include_str!("./model_info.json")).unwrap())
-        .get(name)
-        .cloned()
-        .unwrap_or_default()
+fn builtin_models() -> &'static ModelInfoMap {
+    MODEL_INFO.get_or_init(|| serde_json::from_str(include_str!("./model_info.json")).unwrap())
+}
+
+/// The built-in models, with custom models from `models.json` and the config merged over them.
+#[derive(Clone, Debug, Default)]
+pub struct ModelRegistry {
+    models: ModelInfoMap,
+}
+
+impl ModelRegistry {
+    pub fn builtin() -> Self {
+        Self {
+            models: builtin_models().clone(),
+        }
+    }
+
+    /// Adds `models`, merging entries for known models field by field.
+    pub fn with_models(mut self, models: &ModelInfoMap) -> Self {
+        for (id, info) in models {
+            let id = self.resolve(id);
+            let merged = self.models.remove(&id).unwrap_or_default().merge(info.clone());
+            self.models.insert(id, merged);
+        }
+        self
+    }
+
+    /// The model id for `name`, which may be an alias.
+    pub fn resolve(&self, name: &str) -> String {
+        if self.models.contains_key(name) {
+            return name.to_string();
+        }
+        self.models
+            .iter()
+            .find(|(_, info)| info.aliases.iter().any(|a| a == name))
+            .map(|(id, _)| id.clone())
+            .unwrap_or(name.to_string())
+    }
+
+    pub fn get(&self, name: &str) -> ModelInfo {
+        self.models
+            .get(&self.resolve(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// All models, sorted by id.
+    pub fn list(&self) -> Vec<(String, ModelInfo)> {
+        let mut models: Vec<_> = self
+            .models
+            .iter()
+            .map(|(id, info)| (id.clone(), info.clone()))
+            .collect();
+        models.sort_by(|a, b| a.0.cmp(&b.0));
+        models
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let custom: ModelInfoMap = serde_json::from_str(
+            r#"{
+                "sonnet": { "max_output": 8192 },
+                "llama3.3:70b": { "name": "Llama 3.3", "max_context": 128000, "aliases": ["llama"] }
+            }"#,
+        )
+        .unwrap();
+        let registry = ModelRegistry::builtin().with_models(&custom);
+
+        // Overrides by alias keep the built-in fields that are not set.
+        let sonnet = registry.get("claude-sonnet-4-5");
+        assert_eq!(sonnet.max_output, Some(8192));
+        assert_eq!(sonnet.name.as_deref(), Some("Claude Sonnet 4.5"));
+
+        assert_eq!(registry.resolve("llama"), "llama3.3:70b");
+        assert_eq!(registry.get("llama").max_context, Some(128000));
+        assert_eq!(registry.get("unknown").name, None);
+    }
 }
@@ -0,0 +1,16 @@
+/// How a notice is styled in the transcript.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoticeLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A client-side message shown in the transcript, but never sent to the model.
+#[derive(Clone, Debug)]
+pub struct Notice {
+    /// Number of chat messages before this notice, used to place it in the transcript.
+    pub position: usize,
+    pub level: NoticeLevel,
+    pub text: String,
+}
@@ -1,18 +1,41 @@
 use std::{collections::BTreeMap, path::Path};
 
 use handlebars::Handlebars;
+use serde_json::Value;
+
+use crate::error::Error;
 
 pub fn build_system_prompt() -> String {
-    let handlebars = Handlebars::new();
+    render_prompt(include_str!("../../prompts/BASE.md"))
+}
+
+pub fn build_subagent_prompt() -> String {
+    render_prompt(include_str!("../../prompts/SUBAGENT.md"))
+}
+
+/// Renders a built-in prompt template with the environment information shared by all prompts.
+pub fn render_prompt(template: &str) -> String {
+    render_prompt_with(template, BTreeMap::new()).unwrap()
+}
+
+/// Renders a prompt template with extra variables. Unlike `render_prompt`,
+/// this is meant for user-written templates, so errors are returned.
+pub fn render_prompt_with(
+    template: &str,
+    mut data: BTreeMap<String, Value>,
+) -> Result<String, Error> {
+    let mut handlebars = Handlebars::new();
+    // Prompts are plain text; don't HTML-escape the variables.
+    handlebars.register_escape_fn(handlebars::no_escape);
 
-    let template = include_str!("../../prompts/BASE.md");
-    let mut data = BTreeMap::new();
     data.insert(
         "workingDirectory".to_string(),
-        std::env::current_dir().unwrap(),
+        Value::String(std::env::current_dir()?.display().to_string()),
     );
 
-    handlebars.render_template(template, &data).unwrap()
+    handlebars
+        .render_template(template, &data)
+        .map_err(|e| Error::CommandError(format!("Invalid template: {e}")))
 }
 
 pub async fn read_enchant_md(working_directory: impl AsRef<Path>) -> Option<String> {
@@ -0,0 +1,301 @@
+//! Resolves the API key and endpoint of each provider, from `api-keys.json`
+//! and the environment.
+
+use std::env;
+
+use genai::{
+    Client, ModelIden, ServiceTarget,
+    adapter::AdapterKind,
+    resolver::{self, AuthData, AuthResolver, Endpoint, ServiceTargetResolver},
+};
+
+use crate::agent::config::{ProviderKey, ProviderKeys};
+
+const OPENROUTER_NAMESPACE: &str = "openrouter";
+const OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1/";
+const OPENROUTER_KEY_ENV: &str = "OPENROUTER_API_KEY";
+
+/// Builds a client that authenticates with `keys`.
+pub fn client(keys: &ProviderKeys) -> Client {
+    Client::builder()
+        .with_auth_resolver(auth_resolver(keys))
+        .with_service_target_resolver(service_target_resolver(keys))
+        .build()
+}
+
+pub fn auth_resolver(keys: &ProviderKeys) -> AuthResolver {
+    let keys = keys.clone();
+    AuthResolver::from_resolver_fn(
+        move |model_iden: ModelIden| -> Result<Option<AuthData>, resolver::Error> {
+            let kind = model_iden.adapter_kind;
+            // Custom endpoints are authenticated by the service target resolver.
+            if kind == AdapterKind::Ollama || custom_namespace(&keys, &model_iden).is_some() {
+                return Ok(None);
+            }
+
+            match find_key(&keys, kind, |name| env::var(name).ok()) {
+                Some(api_key) => Ok(Some(AuthData::from_single(api_key))),
+                None => Err(missing_key_error(
+                    kind.as_lower_str(),
+                    kind.default_key_env_name(),
+                )),
+            }
+        },
+    )
+}
+
+/// Points OpenRouter, OpenAI-compatible and self-hosted Ollama models at their endpoints.
+pub fn service_target_resolver(keys: &ProviderKeys) -> ServiceTargetResolver {
+    let keys = keys.clone();
+    ServiceTargetResolver::from_resolver_fn(
+        move |mut target: ServiceTarget| -> Result<ServiceTarget, resolver::Error> {
+            let Some((namespace, model)) = custom_namespace(&keys, &target.model) else {
+                if target.model.adapter_kind == AdapterKind::Ollama
+                    && let Some(base_url) = ollama_base_url(&keys)
+                {
+                    target.endpoint = endpoint(base_url);
+                }
+                return Ok(target);
+            };
+
+            let (base_url, api_key) = match keys.get(&namespace) {
+                Some(ProviderKey::OpenAICompatible { base_url, api_key }) => {
+                    (base_url.clone(), api_key.clone().unwrap_or_default())
+                }
+                _ => {
+                    let api_key = find_openrouter_key(&keys, |name| env::var(name).ok())
+                        .ok_or_else(|| {
+                            missing_key_error(OPENROUTER_NAMESPACE, Some(OPENROUTER_KEY_ENV))
+                        })?;
+                    (OPENROUTER_BASE_URL.to_string(), api_key)
+                }
+            };
+
+            target.endpoint = endpoint(&base_url);
+            target.auth = AuthData::from_single(api_key);
+            target.model = ModelIden::new(AdapterKind::OpenAI, model);
+            Ok(target)
+        },
+    )
+}
+
+/// The API key for `kind`: from the entry named after the provider, the
+/// first other entry of that provider by name, or the provider's
+/// environment variable.
+fn find_key(
+    keys: &ProviderKeys,
+    kind: AdapterKind,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let key_of = |key: &ProviderKey| -> Option<String> {
+        let api_key = match (key, kind) {
+            (ProviderKey::OpenAI { api_key }, AdapterKind::OpenAI | AdapterKind::OpenAIResp)
+            | (ProviderKey::Anthropic { api_key }, AdapterKind::Anthropic)
+            | (ProviderKey::Gemini { api_key }, AdapterKind::Gemini)
+            | (ProviderKey::Groq { api_key }, AdapterKind::Groq)
+            | (ProviderKey::XAi { api_key }, AdapterKind::Xai)
+            | (ProviderKey::DeepSeek { api_key }, AdapterKind::DeepSeek) => api_key,
+            _ => return None,
+        };
+        Some(api_key.clone())
+    };
+
+    keys.get(kind.as_lower_str())
+        .and_then(key_of)
+        .or_else(|| by_name(keys).find_map(key_of))
+        .or_else(|| kind.default_key_env_name().and_then(env))
+}
+
+fn find_openrouter_key(
+    keys: &ProviderKeys,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    by_name(keys)
+        .find_map(|key| match key {
+            ProviderKey::OpenRouter { api_key } => Some(api_key.clone()),
+            _ => None,
+        })
+        .or_else(|| env(OPENROUTER_KEY_ENV))
+}
+
+/// The entries of `keys`, ordered by name so that the one picked among
+/// several of the same provider doesn't change from run to run.
+fn by_name(keys: &ProviderKeys) -> impl Iterator<Item = &ProviderKey> {
+    let mut entries: Vec<_> = keys.iter().collect();
+    entries.sort_by_key(|(name, _)| *name);
+    entries.into_iter().map(|(_, key)| key)
+}
+
+/// Splits `<namespace>::<model>` when the namespace names an OpenAI-compatible
+/// provider in `keys`, or OpenRouter.
+fn custom_namespace(keys: &ProviderKeys, model: &ModelIden) -> Option<(String, String)> {
+    let (namespace, name) = model.model_name.split_once("::")?;
+    let is_custom = match keys.get(namespace) {
+        Some(ProviderKey::OpenAICompatible { .. } | ProviderKey::OpenRouter { .. }) => true,
+        Some(_) => false,
+        None => namespace == OPENROUTER_NAMESPACE,
+    };
+    is_custom.then(|| (namespace.to_string(), name.to_string()))
+}
+
+fn ollama_base_url(keys: &ProviderKeys) -> Option<&str> {
+    by_name(keys).find_map(|key| match key {
+        ProviderKey::Ollama { base_url } => base_url.as_deref(),
+        _ => None,
+    })
+}
+
+/// Base URLs must end with `/` for the API paths to be appended to them.
+fn endpoint(base_url: &str) -> Endpoint {
+    if base_url.ends_with('/') {
+        Endpoint::from_owned(base_url.to_string())
+    } else {
+        Endpoint::from_owned(format!("{base_url}/"))
+    }
+}
+
+fn missing_key_error(provider: &str, env_name: Option<&str>) -> resolver::Error {
+    let mut message = format!(
+        "No API key for {provider}. Add a \"{provider}\" entry to ~/.enchant/api-keys.json"
+    );
+    if let Some(env_name) = env_name {
+        message.push_str(&format!(" or set {env_name}"));
+    }
+    resolver::Error::Custom(message + ".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> ProviderKeys {
+        serde_json::from_str(
+            r#"{
+                "anthropic": { "provider": "anthropic", "api_key": "sk-ant" },
+                "work-groq": { "provider": "groq", "api_key": "gsk" },
+                "local": { "provider": "openai_compatible", "base_url": "http://127.0.0.1:8080/v1" }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_find_key() {
+        let keys = keys();
+        let env = |name: &str| (name == "GEMINI_API_KEY").then(|| "from-env".to_string());
+        assert_eq!(
+            find_key(&keys, AdapterKind::Anthropic, env).as_deref(),
+            Some("sk-ant")
+        );
+        assert_eq!(
+            find_key(&keys, AdapterKind::Groq, env).as_deref(),
+            Some("gsk")
+        );
+        assert_eq!(
+            find_key(&keys, AdapterKind::Gemini, env).as_deref(),
+            Some("from-env")
+        );
+        assert_eq!(find_key(&keys, AdapterKind::DeepSeek, env), None);
+
+        let mut keys = keys;
+        for name in ["openai-work", "openai-personal", "openai-team"] {
+            keys.insert(
+                name.to_string(),
+                ProviderKey::OpenAI {
+                    api_key: name.to_string(),
+                },
+            );
+        }
+        assert_eq!(
+            find_key(&keys, AdapterKind::OpenAI, env).as_deref(),
+            Some("openai-personal")
+        );
+    }
+
+    #[test]
+    fn test_openrouter_entry() {
+        let mut keys = keys();
+        keys.insert(
+            "openrouter".to_string(),
+            ProviderKey::OpenRouter {
+                api_key: "sk-or".to_string(),
+            },
+        );
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let target = runtime
+            .block_on(client(&keys).resolve_service_target("openrouter::some/model"))
+            .unwrap();
+        assert_eq!(target.endpoint.base_url(), OPENROUTER_BASE_URL);
+        assert_eq!(target.auth.single_key_value().unwrap(), "sk-or");
+        assert_eq!(target.model.adapter_kind, AdapterKind::OpenAI);
+        assert_eq!(&*target.model.model_name, "some/model");
+    }
+
+    #[test]
+    fn test_openai_compatible() {
+        use std::{
+            io::{Read, Write},
+            net::TcpListener,
+        };
+
+        use genai::chat::ChatRequest;
+
+        // A mock provider answering a single chat completion.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 64 * 1024];
+            let len = stream.read(&mut request).unwrap();
+            let body = r#"{"id":"1","object":"chat.completion","model":"qwen3","choices":[{"index":0,"message":{"role":"assistant","content":"pong"},"finish_reason":"stop"}],"usage":{"prompt_tokens":1,"completion_tokens":1,"total_tokens":2}}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            String::from_utf8_lossy(&request[..len]).to_string()
+        });
+
+        let mut keys = keys();
+        keys.insert(
+            "local".to_string(),
+            ProviderKey::OpenAICompatible {
+                base_url: format!("http://127.0.0.1:{port}/v1"),
+                api_key: Some("local-key".to_string()),
+            },
+        );
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime
+            .block_on(client(&keys).exec_chat("local::qwen3", ChatRequest::from_user("ping"), None))
+            .unwrap();
+        assert_eq!(response.first_text(), Some("pong"));
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions"));
+        assert!(
+            request
+                .to_lowercase()
+                .contains("authorization: bearer local-key")
+        );
+    }
+
+    #[test]
+    fn test_missing_key() {
+        let no_env = |_: &str| None;
+        assert_eq!(find_openrouter_key(&keys(), no_env), None);
+        assert_eq!(find_key(&keys(), AdapterKind::DeepSeek, no_env), None);
+
+        let mut keys = keys();
+        keys.insert(
+            "router".to_string(),
+            ProviderKey::OpenRouter {
+                api_key: "sk-or".to_string(),
+            },
+        );
+        assert_eq!(find_openrouter_key(&keys, no_env).as_deref(), Some("sk-or"));
+
+        let err = missing_key_error(OPENROUTER_NAMESPACE, Some(OPENROUTER_KEY_ENV));
+        assert!(err.to_string().contains("or set OPENROUTER_API_KEY"));
+    }
+}
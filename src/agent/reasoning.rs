@@ -0,0 +1,91 @@
+//! Reasoning effort and thinking budgets.
+
+use std::fmt;
+
+use genai::chat::ReasoningEffort;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningLevel {
+    Off,
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+/// How much a model reasons before answering: a level, or a thinking budget in tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Reasoning {
+    Budget(u32),
+    Level(ReasoningLevel),
+}
+
+impl Default for Reasoning {
+    fn default() -> Self {
+        Self::Level(ReasoningLevel::Off)
+    }
+}
+
+impl Reasoning {
+    /// The level used when reasoning is turned on without one.
+    pub const ON: Self = Self::Level(ReasoningLevel::Medium);
+
+    /// Parses `off`, `minimal`, `low`, `medium`, `high` or a number of tokens.
+    pub fn parse(value: &str) -> Option<Self> {
+        if let Ok(budget) = value.parse() {
+            return Some(Self::Budget(budget));
+        }
+        serde_json::from_value(serde_json::Value::String(value.to_lowercase())).ok()
+    }
+
+    pub fn is_on(&self) -> bool {
+        !matches!(
+            self,
+            Self::Level(ReasoningLevel::Off) | Self::Level(ReasoningLevel::Minimal)
+        )
+    }
+
+    /// The option to send, for a model that supports reasoning.
+    pub fn effort(&self) -> ReasoningEffort {
+        match self {
+            Self::Budget(budget) => ReasoningEffort::Budget(*budget),
+            Self::Level(ReasoningLevel::Off | ReasoningLevel::Minimal) => ReasoningEffort::Minimal,
+            Self::Level(ReasoningLevel::Low) => ReasoningEffort::Low,
+            Self::Level(ReasoningLevel::Medium) => ReasoningEffort::Medium,
+            Self::Level(ReasoningLevel::High) => ReasoningEffort::High,
+        }
+    }
+}
+
+impl fmt::Display for Reasoning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Budget(budget) => write!(f, "{budget} tokens"),
+            Self::Level(level) => write!(f, "{}", format!("{level:?}").to_lowercase()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Reasoning::parse("8000"), Some(Reasoning::Budget(8000)));
+        assert_eq!(
+            Reasoning::parse("High"),
+            Some(Reasoning::Level(ReasoningLevel::High))
+        );
+        assert_eq!(Reasoning::parse("lots"), None);
+        assert!(!Reasoning::parse("off").unwrap().is_on());
+        assert_eq!(
+            serde_json::from_str::<Reasoning>("16000").unwrap(),
+            Reasoning::Budget(16000)
+        );
+    }
+}
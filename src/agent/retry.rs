@@ -0,0 +1,51 @@
+//! Backoff for model calls that failed with a transient error.
+
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::error::Error;
+
+/// Retries of a model call before its error is surfaced.
+pub const MAX_RETRIES: u32 = 5;
+
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// The delay before retry `attempt` (starting at 1) of a call that failed with
+/// `err`, or `None` if it should not be retried.
+pub fn retry_delay(err: &Error, attempt: u32) -> Option<Duration> {
+    if !err.is_transient() || attempt > MAX_RETRIES {
+        return None;
+    }
+    if let Some(delay) = err.retry_after() {
+        return Some(delay.min(MAX_DELAY));
+    }
+    let delay = BASE_DELAY * 2u32.pow(attempt - 1);
+    // Jitter, so that parallel sessions don't retry in lockstep.
+    let jitter = Duration::from_millis(rand::rng().random_range(0..500));
+    Some((delay + jitter).min(MAX_DELAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let err = Error::Overloaded("Overloaded".to_string());
+        assert!(retry_delay(&err, 1).unwrap() < Duration::from_secs(2));
+        assert!(retry_delay(&err, 4).unwrap() >= Duration::from_secs(8));
+        assert_eq!(retry_delay(&err, MAX_RETRIES + 1), None);
+
+        let err = Error::RateLimited {
+            message: "Slow down".to_string(),
+            retry_after: Some(Duration::from_secs(7)),
+        };
+        assert_eq!(retry_delay(&err, 1), Some(Duration::from_secs(7)));
+        assert_eq!(
+            retry_delay(&Error::AuthError("Invalid key".to_string()), 1),
+            None
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Extended thinking across tool calls for Anthropic models.
+//!
+//! With thinking on, the request that follows a tool call must send back the
+//! thinking blocks that preceded the call, signatures included. genai keeps
+//! neither the signatures nor a way to send thinking blocks, so the blocks are
+//! taken from the raw response body, and the requests that replay them are
+//! made here instead.
+
+use std::collections::HashMap;
+
+use genai::{
+    ModelIden, ServiceTarget,
+    chat::{
+        Binary, BinarySource, CacheControl, ChatMessage, ChatResponse, ChatRole, ContentPart,
+        MessageContent, PromptTokensDetails, ReasoningEffort, Tool, ToolCall, Usage,
+    },
+    webc,
+};
+use serde_json::{Value, json};
+
+use crate::error::Error;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Thinking blocks of assistant messages (message index -> blocks), as
+/// returned by Anthropic.
+pub type ThinkingBlocks = HashMap<usize, Vec<Value>>;
+
+/// The thinking budget for `effort`, the same as genai's, or `None` if
+/// thinking is off.
+pub fn thinking_budget(effort: &ReasoningEffort) -> Option<u32> {
+    match effort {
+        ReasoningEffort::Minimal => None,
+        ReasoningEffort::Low => Some(1024),
+        ReasoningEffort::Medium => Some(8000),
+        ReasoningEffort::High => Some(24000),
+        ReasoningEffort::Budget(budget) => Some(*budget),
+    }
+}
+
+/// The thinking and redacted thinking blocks of a raw Anthropic response.
+pub fn thinking_blocks(body: &Value) -> Vec<Value> {
+    body["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|block| {
+            matches!(
+                block["type"].as_str(),
+                Some("thinking" | "redacted_thinking")
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+/// Sends `messages` to an Anthropic model with a thinking budget, each
+/// assistant message led by its blocks from `thinking`. The raw body is kept
+/// in the response, as with genai's `capture_raw_body`.
+pub async fn exec_chat(
+    target: ServiceTarget,
+    messages: &[ChatMessage],
+    thinking: &ThinkingBlocks,
+    tools: &[Tool],
+    budget: u32,
+) -> Result<ChatResponse, Error> {
+    let ServiceTarget {
+        endpoint,
+        auth,
+        model,
+    } = target;
+    let api_key = auth
+        .single_key_value()
+        .map_err(|e| Error::AuthError(e.to_string()))?;
+    let body = request_body(&model, messages, thinking, tools, budget);
+
+    let network = |e: reqwest::Error| Error::NetworkError(e.to_string());
+    let response = reqwest::Client::new()
+        .post(format!("{}messages", endpoint.base_url()))
+        .header("x-api-key", api_key)
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .header("content-type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(network)?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.text().await.map_err(network)?;
+    if !status.is_success() {
+        // Classified like the failures of requests made by genai.
+        return Err(Error::from(genai::Error::WebModelCall {
+            model_iden: model,
+            webc_error: webc::Error::ResponseFailedStatus {
+                status,
+                body,
+                headers: Box::new(headers),
+            },
+        }));
+    }
+    Ok(chat_response(model, serde_json::from_str(&body)?))
+}
+
+fn request_body(
+    model: &ModelIden,
+    messages: &[ChatMessage],
+    thinking: &ThinkingBlocks,
+    tools: &[Tool],
+    budget: u32,
+) -> Value {
+    let mut system = vec![];
+    let mut turns = vec![];
+    for (index, message) in messages.iter().enumerate() {
+        let cached = message
+            .options
+            .as_ref()
+            .is_some_and(|o| matches!(o.cache_control, Some(CacheControl::Ephemeral)));
+        let (role, mut blocks) = match message.role {
+            ChatRole::System => {
+                let text = message.content.joined_texts().unwrap_or_default();
+                system.extend(with_cache_control(cached, vec![text_block(text)]));
+                continue;
+            }
+            ChatRole::Assistant => {
+                let mut blocks = thinking.get(&index).cloned().unwrap_or_default();
+                blocks.extend(message.content.parts().iter().filter_map(content_block));
+                ("assistant", blocks)
+            }
+            // Tool results are sent by the user.
+            ChatRole::User | ChatRole::Tool => (
+                "user",
+                message
+                    .content
+                    .parts()
+                    .iter()
+                    .filter_map(content_block)
+                    .collect(),
+            ),
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+        blocks = with_cache_control(cached, blocks);
+        turns.push(json!({ "role": role, "content": blocks }));
+    }
+
+    let model_name = model.model_name.rsplit("::").next().unwrap_or_default();
+    let mut body = json!({
+        "model": model_name,
+        "max_tokens": max_tokens(model_name).max(budget + 1),
+        "thinking": { "type": "enabled", "budget_tokens": budget },
+        "messages": turns,
+    });
+    if !system.is_empty() {
+        body["system"] = json!(system);
+    }
+    if !tools.is_empty() {
+        body["tools"] = tools
+            .iter()
+            .map(|tool| {
+                let mut value = json!({ "name": tool.name, "input_schema": tool.schema });
+                if let Some(description) = &tool.description {
+                    value["description"] = json!(description);
+                }
+                value
+            })
+            .collect();
+    }
+    body
+}
+
+fn text_block(text: impl Into<String>) -> Value {
+    json!({ "type": "text", "text": text.into() })
+}
+
+/// A content block for a part of a message. Images given by URL are left
+/// out, as genai does, since Anthropic only takes them inline.
+fn content_block(part: &ContentPart) -> Option<Value> {
+    Some(match part {
+        ContentPart::Text(text) => text_block(text),
+        ContentPart::ToolCall(call) => json!({
+            "type": "tool_use",
+            "id": call.call_id,
+            "name": call.fn_name,
+            "input": call.fn_arguments,
+        }),
+        ContentPart::ToolResponse(response) => json!({
+            "type": "tool_result",
+            "tool_use_id": response.call_id,
+            "content": response.content,
+        }),
+        ContentPart::Binary(binary) => {
+            let Binary {
+                content_type,
+                source,
+                ..
+            } = binary;
+            let kind = if binary.is_image() {
+                "image"
+            } else {
+                "document"
+            };
+            let source = match source {
+                BinarySource::Base64(data) => {
+                    json!({ "type": "base64", "media_type": content_type, "data": data })
+                }
+                BinarySource::Url(_) if binary.is_image() => return None,
+                BinarySource::Url(url) => json!({ "type": "url", "url": url }),
+            };
+            json!({ "type": kind, "source": source })
+        }
+    })
+}
+
+/// Marks the last block as a cache breakpoint.
+fn with_cache_control(cached: bool, mut blocks: Vec<Value>) -> Vec<Value> {
+    if cached && let Some(last) = blocks.last_mut() {
+        last["cache_control"] = json!({ "type": "ephemeral" });
+    }
+    blocks
+}
+
+/// The default `max_tokens` of genai, so that every step of a prompt gets
+/// the same limit.
+fn max_tokens(model_name: &str) -> u32 {
+    if model_name.contains("claude-opus-4") {
+        32000
+    } else if model_name.contains("claude-3-5") {
+        8192
+    } else if model_name.contains("3-opus") || model_name.contains("3-haiku") {
+        4096
+    } else {
+        64000
+    }
+}
+
+fn chat_response(model: ModelIden, body: Value) -> ChatResponse {
+    let mut content = MessageContent::default();
+    let mut reasoning = vec![];
+    for block in body["content"].as_array().into_iter().flatten() {
+        match block["type"].as_str() {
+            Some("text") => content.push(block["text"].as_str().unwrap_or_default()),
+            Some("thinking") => reasoning.push(block["thinking"].as_str().unwrap_or_default()),
+            Some("tool_use") => content.push(ToolCall {
+                call_id: block["id"].as_str().unwrap_or_default().to_string(),
+                fn_name: block["name"].as_str().unwrap_or_default().to_string(),
+                fn_arguments: block["input"].clone(),
+            }),
+            _ => {}
+        }
+    }
+
+    let tokens = |key: &str| body["usage"][key].as_i64().unwrap_or(0) as i32;
+    let (cache_writes, cache_reads) = (
+        tokens("cache_creation_input_tokens"),
+        tokens("cache_read_input_tokens"),
+    );
+    // Counted like genai does: the prompt includes the cached tokens.
+    let prompt_tokens = tokens("input_tokens") + cache_writes + cache_reads;
+    let completion_tokens = tokens("output_tokens");
+    let usage = Usage {
+        prompt_tokens: Some(prompt_tokens),
+        prompt_tokens_details: (cache_writes > 0 || cache_reads > 0).then_some(
+            PromptTokensDetails {
+                cache_creation_tokens: Some(cache_writes),
+                cached_tokens: Some(cache_reads),
+                audio_tokens: None,
+            },
+        ),
+        completion_tokens: Some(completion_tokens),
+        completion_tokens_details: None,
+        total_tokens: Some(prompt_tokens + completion_tokens),
+    };
+
+    ChatResponse {
+        content,
+        reasoning_content: (!reasoning.is_empty()).then(|| reasoning.join("\n")),
+        provider_model_iden: model.from_optional_name(body["model"].as_str().map(str::to_string)),
+        model_iden: model,
+        usage,
+        captured_raw_body: Some(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use genai::{
+        adapter::AdapterKind,
+        chat::ToolResponse,
+        resolver::{AuthData, Endpoint},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_exec_chat() {
+        // A mock Anthropic API answering a single request.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 64 * 1024];
+            let len = stream.read(&mut request).unwrap();
+            let body = r#"{"model":"claude-sonnet-4-5-20250929","content":[{"type":"thinking","thinking":"Done.","signature":"sig-2"},{"type":"text","text":"pong"}],"usage":{"input_tokens":10,"cache_read_input_tokens":90,"output_tokens":5}}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            String::from_utf8_lossy(&request[..len]).to_string()
+        });
+
+        let call = ToolCall {
+            call_id: "call-1".to_string(),
+            fn_name: "Read".to_string(),
+            fn_arguments: json!({ "path": "Cargo.toml" }),
+        };
+        let messages = vec![
+            ChatMessage::system("prompt").with_options(CacheControl::Ephemeral),
+            ChatMessage::user("ping"),
+            ChatMessage::from(vec![call]),
+            ChatMessage::from(ToolResponse::new("call-1", "[package]"))
+                .with_options(CacheControl::Ephemeral),
+        ];
+        let signed = json!({ "type": "thinking", "thinking": "Read it.", "signature": "sig-1" });
+        let thinking = ThinkingBlocks::from([(2, vec![signed.clone()])]);
+        let target = ServiceTarget {
+            endpoint: Endpoint::from_owned(format!("http://127.0.0.1:{port}/v1/")),
+            auth: AuthData::from_single("sk-ant"),
+            model: ModelIden::new(AdapterKind::Anthropic, "anthropic::claude-sonnet-4-5"),
+        };
+        let response = exec_chat(target, &messages, &thinking, &[], 8000)
+            .await
+            .unwrap();
+        assert_eq!(response.first_text(), Some("pong"));
+        assert_eq!(response.reasoning_content.as_deref(), Some("Done."));
+        assert_eq!(response.usage.prompt_tokens, Some(100));
+        let body = response.captured_raw_body.unwrap();
+        assert_eq!(thinking_blocks(&body)[0]["signature"], "sig-2");
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /v1/messages"));
+        assert!(request.contains("x-api-key: sk-ant"));
+        let request: Value =
+            serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(request["model"], "claude-sonnet-4-5");
+        assert_eq!(request["thinking"]["budget_tokens"], 8000);
+        assert_eq!(request["system"][0]["cache_control"]["type"], "ephemeral");
+        let turns = request["messages"].as_array().unwrap();
+        assert_eq!(turns[1]["content"][0], signed);
+        assert_eq!(turns[1]["content"][1]["type"], "tool_use");
+        assert_eq!(turns[2]["role"], "user");
+        assert_eq!(turns[2]["content"][0]["type"], "tool_result");
+        assert_eq!(turns[2]["content"][0]["cache_control"]["type"], "ephemeral");
+    }
+}
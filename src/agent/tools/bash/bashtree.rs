@@ -46,11 +46,11 @@ impl Expression {
 
 impl Command {
     pub fn is_safe(&self) -> bool {
-        match self.program.as_str() {
+        matches!(
+            self.program.as_str(),
             "cat" | "cd" | "echo" | "false" | "grep" | "head" | "ls" | "nl" | "pwd" | "tail"
-            | "true" | "wc" | "which" => true,
-            _ => false,
-        }
+                | "true" | "wc" | "which"
+        )
     }
 
     pub fn is_allowed(&self, cfg: &BashConfig) -> bool {
@@ -78,7 +78,7 @@ impl Command {
             if !wildcard {
                 return args.len() == self.args.len();
             }
-            return true;
+            true
         })
     }
 }
@@ -39,6 +39,10 @@ impl Tool for Bash {
         Ok(perm)
     }
 
+    fn may_need_approval() -> bool {
+        true
+    }
+
     fn describe_action(input: &Self::Input) -> String {
         format!("Bash({})", input.command)
     }
@@ -33,7 +33,7 @@ pub fn parse_statements(input: &str) -> Result<Vec<CompoundListItem>, ToolError>
 
     let statements = command_list
         .into_iter()
-        .nth(0)
+        .next()
         .ok_or_else(|| {
             ToolError::BashError("Only bash operators that are permitted are &&, ||, |".to_string())
         })?
@@ -48,7 +48,7 @@ pub fn parse_statement(input: &str) -> Result<CompoundListItem, ToolError> {
             "Only bash operators that are permitted are &&, ||, |".to_string(),
         ));
     }
-    let statement = statements.into_iter().nth(0).ok_or_else(|| {
+    let statement = statements.into_iter().next().ok_or_else(|| {
         ToolError::BashError("Only bash operators that are permitted are &&, ||, |".to_string())
     })?;
     if matches!(statement.1, SeparatorOperator::Async) {
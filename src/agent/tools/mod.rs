@@ -1,4 +1,8 @@
 use crate::agent::tools::tool::Tool;
+use crate::agent::tools::{
+    bash::Bash, edit::Edit, glob::Glob, grep::Grep, ls::Ls, read::Read, tool::WrappedTool,
+    write::Write,
+};
 
 pub mod bash;
 pub mod edit;
@@ -7,7 +11,21 @@ pub mod grep;
 pub mod ls;
 pub mod permission;
 pub mod read;
+pub mod task;
 pub mod tool;
 pub mod tool_error;
 pub mod walk_builder;
 pub mod write;
+
+/// The built-in tools, in the order they are presented to the model.
+pub fn builtin_tools() -> Vec<Box<dyn WrappedTool + Send + Sync + 'static>> {
+    vec![
+        Box::new(Read),
+        Box::new(Glob),
+        Box::new(Grep),
+        Box::new(Ls),
+        Box::new(Edit),
+        Box::new(Write),
+        Box::new(Bash),
+    ]
+}
@@ -0,0 +1,294 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use genai::chat::{ChatRole, Tool as AITool};
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::agent::{
+    DEFAULT_MODEL, Session, ThinkResult,
+    agents::AgentDefinition,
+    budget::BudgetConfig,
+    config::{Config, ProviderKeys},
+    prompt::{build_subagent_prompt, render_prompt_with},
+    retry::retry_delay,
+    tools::{
+        permission::Permission,
+        tool::{ToolOutput, ToolPreview, Toolset, WrappedTool},
+        tool_error::ToolError,
+    },
+};
+
+/// Upper bound on think steps for a single sub-agent run.
+const MAX_STEPS: usize = 100;
+
+/// Delegates a task to a sub-agent with its own history and a restricted toolset.
+pub struct Task {
+    /// Tools the sub-agent's toolset is picked from.
+    tools: Arc<Toolset>,
+    /// Named agents that can be delegated to, in addition to the general-purpose one.
+    agents: Vec<AgentDefinition>,
+    config: Config,
+    api_keys: ProviderKeys,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TaskInput {
+    /// A short (3-5 word) description of the task.
+    pub description: String,
+    /// The task for the sub-agent to perform. Include all the context it needs.
+    pub prompt: String,
+    /// Name of the agent to delegate to. Omit for a general-purpose sub-agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+}
+
+impl Task {
+    pub fn new(
+        tools: Arc<Toolset>,
+        agents: Vec<AgentDefinition>,
+        config: Config,
+        api_keys: ProviderKeys,
+    ) -> Self {
+        Self {
+            tools,
+            agents,
+            config,
+            api_keys,
+        }
+    }
+
+    /// Runs the sub-agent within `budget`, what is left of the caller's.
+    async fn run(&self, input: TaskInput, budget: BudgetConfig) -> Result<ToolOutput, ToolError> {
+        let agent = match &input.agent {
+            Some(name) => Some(self.agents.iter().find(|a| &a.name == name).ok_or_else(|| {
+                ToolError::Error {
+                    message: format!("Unknown agent: {name}"),
+                }
+            })?),
+            None => None,
+        };
+
+        let model = agent
+            .and_then(|a| a.model.clone())
+            .or(self.config.subagent.model.clone())
+            .or(self.config.default_model.clone())
+            .unwrap_or(DEFAULT_MODEL.to_string());
+        let tools = agent
+            .and_then(|a| a.tools.clone())
+            .unwrap_or_else(|| self.config.subagent.tools());
+        let system_prompt = match agent {
+            Some(agent) => {
+                render_prompt_with(&agent.prompt, Default::default()).map_err(subagent_error)?
+            }
+            None => build_subagent_prompt(),
+        };
+
+        let config = Config {
+            budget,
+            ..self.config.clone()
+        };
+        let mut session = Session::new_subagent(
+            config,
+            self.api_keys.clone(),
+            model,
+            self.tools.subset(&tools).0,
+            system_prompt,
+        )
+        .map_err(subagent_error)?;
+
+        // The usage is reported even if the sub-agent fails, so that it still
+        // counts against the caller's budget.
+        let output = match drive(&mut session, input.prompt).await {
+            Ok(()) => {
+                let report = session
+                    .messages
+                    .iter()
+                    .rev()
+                    .find(|m| matches!(m.role, ChatRole::Assistant))
+                    .and_then(|m| m.content.joined_texts())
+                    .unwrap_or_default();
+                ToolOutput::new(report)
+            }
+            Err(message) => ToolOutput::failure(message),
+        };
+        let tool_calls = session.budget_usage.tool_calls;
+        Ok(output
+            .with_activity(collect_activity(&session))
+            .with_usage(session.usage.records.clone(), tool_calls))
+    }
+}
+
+/// Runs the sub-agent on `prompt` until it is done.
+async fn drive(session: &mut Session, prompt: String) -> Result<(), String> {
+    session.message(prompt).map_err(|e| format!("Sub-agent failed: {e:?}"))?;
+    for _ in 0..MAX_STEPS {
+        let mut attempt = 0;
+        let result = loop {
+            match session.think_step().await {
+                Ok(result) => break result,
+                Err(err) => {
+                    attempt += 1;
+                    let Some(delay) = retry_delay(&err, attempt) else {
+                        return Err(format!("Sub-agent failed: {err:?}"));
+                    };
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        };
+        match result {
+            ThinkResult::Done => return Ok(()),
+            ThinkResult::Continue => {}
+            ThinkResult::BudgetReached(limit) => {
+                return Err(format!("Sub-agent stopped: budget reached ({limit})"));
+            }
+            // Tools that may need approval are withheld from sub-agents
+            // when the config loads; deny anything that still asks.
+            ThinkResult::NeedsPermission(requests) => {
+                for request in requests {
+                    session.deny_permission(&request.call_id);
+                }
+            }
+        }
+    }
+    Err(format!("Sub-agent did not finish within {MAX_STEPS} steps"))
+}
+
+/// Describes every tool call the sub-agent made, in order.
+fn collect_activity(session: &Session) -> Vec<String> {
+    session
+        .messages
+        .iter()
+        .flat_map(|m| m.content.tool_calls())
+        .map(|call| session.tools.describe_action(&call.fn_name, &call.fn_arguments))
+        .collect()
+}
+
+fn subagent_error(err: crate::error::Error) -> ToolError {
+    ToolError::Error {
+        message: format!("Sub-agent failed: {err:?}"),
+    }
+}
+
+#[async_trait]
+impl WrappedTool for Task {
+    async fn call(&self, input: Value) -> Result<String, ToolError> {
+        let output = self.call_with_output(input).await?;
+        if output.failed {
+            return Err(ToolError::Error {
+                message: output.content,
+            });
+        }
+        Ok(output.content)
+    }
+
+    async fn call_with_output(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        self.call_with_budget(input, self.config.budget.clone()).await
+    }
+
+    async fn call_with_budget(
+        &self,
+        input: Value,
+        budget: BudgetConfig,
+    ) -> Result<ToolOutput, ToolError> {
+        let input: TaskInput = serde_json::from_value(input).map_err(|e| ToolError::Error {
+            message: format!("Invalid tool input: {e}"),
+        })?;
+        self.run(input, budget).await
+    }
+
+    fn to_tool(&self) -> AITool {
+        let mut description = include_str!("./task.md").to_string();
+        if !self.agents.is_empty() {
+            description.push_str("\nAvailable agents:\n");
+            for agent in &self.agents {
+                description.push_str(&format!("- {}: {}\n", agent.name, agent.description));
+            }
+        }
+
+        AITool {
+            name: "Task".to_string(),
+            description: Some(description),
+            schema: Some(schema_for!(TaskInput).to_value()),
+            config: None,
+        }
+    }
+
+    fn requires_permission(
+        &self,
+        _session: &Session,
+        _input: &Value,
+    ) -> Result<Permission, ToolError> {
+        Ok(Permission::Implicit)
+    }
+
+    fn describe_action(&self, input: &Value) -> String {
+        match serde_json::from_value::<TaskInput>(input.clone()) {
+            Ok(TaskInput {
+                agent: Some(agent),
+                description,
+                ..
+            }) => format!("Task({agent}: {description})"),
+            Ok(input) => format!("Task({})", input.description),
+            Err(_) => "Task".to_string(),
+        }
+    }
+
+    async fn generate_preview(&self, _input: &Value) -> Option<ToolPreview> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::agent::{config::SubagentConfig, tests::mock_provider};
+
+    fn task(port: u16) -> Task {
+        let keys: ProviderKeys = serde_json::from_value(json!({
+            "mock": { "provider": "openai_compatible", "base_url": format!("http://127.0.0.1:{port}/v1") },
+        }))
+        .unwrap();
+        let config = Config {
+            subagent: SubagentConfig {
+                model: Some("mock::m".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Task::new(Arc::new(Toolset::new(vec![])), vec![], config, keys)
+    }
+
+    #[tokio::test]
+    async fn test_task() {
+        let task = task(mock_provider("200 OK"));
+        let output = task
+            .call_with_output(json!({ "description": "Ping", "prompt": "ping" }))
+            .await
+            .unwrap();
+        assert_eq!(output.content, "pong");
+        assert!(output.activity.is_empty());
+        assert_eq!(output.usage.len(), 1);
+        assert_eq!(output.usage[0].tokens.total(), 2);
+
+        let budget = BudgetConfig {
+            max_tokens: Some(0),
+            ..Default::default()
+        };
+        let output = task
+            .call_with_budget(json!({ "description": "Ping", "prompt": "ping" }), budget)
+            .await
+            .unwrap();
+        assert!(output.failed);
+        assert_eq!(output.content, "Sub-agent stopped: budget reached (0 tokens)");
+
+        let err = task
+            .call_with_output(json!({ "description": "Ping", "prompt": "ping", "agent": "reviewer" }))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:?}").contains("Unknown agent: reviewer"));
+    }
+}
@@ -1,12 +1,17 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
-use genai::chat::Tool as AITool;
+use genai::chat::{Binary, Tool as AITool};
 use schemars::{JsonSchema, schema_for};
 use serde::{Serialize, de::DeserializeOwned};
 use serde_json::Value;
 
-use crate::agent::{Session, tools::{permission::Permission, tool_error::ToolError}};
+use crate::agent::{
+    Session,
+    budget::BudgetConfig,
+    tools::{permission::Permission, tool_error::ToolError},
+    usage::UsageRecord,
+};
 
 /// Preview content for permission prompts
 #[derive(Clone, Debug)]
@@ -34,6 +39,11 @@ pub trait Tool {
         Ok(Permission::Implicit)
     }
 
+    /// Whether `requires_permission` can return anything but Implicit.
+    fn may_need_approval() -> bool {
+        false
+    }
+
     /// Returns a human-readable description of the action for permission prompts.
     fn describe_action(input: &Self::Input) -> String {
         format!(
@@ -75,6 +85,58 @@ impl ToolInfo {
     }
 }
 
+/// The full result of a tool call.
+///
+/// Most tools only produce text, but some (like `Task`) also report nested
+/// activity that is shown collapsed under the call in the transcript, and
+/// MCP tools can return images, which are sent to the model after the result.
+#[derive(Clone, Debug, Default)]
+pub struct ToolOutput {
+    pub content: String,
+    pub activity: Vec<String>,
+    pub images: Vec<Binary>,
+    /// Model calls made by the tool itself, e.g. by a sub-agent. They count
+    /// against the caller's budget.
+    pub usage: Vec<UsageRecord>,
+    /// Tool calls made by the tool itself.
+    pub tool_calls: usize,
+    /// Whether the tool failed, with `content` as the error. Unlike an `Err`,
+    /// this keeps the activity and usage of a sub-agent that gave up.
+    pub failed: bool,
+}
+
+impl ToolOutput {
+    pub fn new(content: String) -> Self {
+        Self {
+            content,
+            ..Default::default()
+        }
+    }
+
+    pub fn failure(message: String) -> Self {
+        Self {
+            failed: true,
+            ..Self::new(message)
+        }
+    }
+
+    pub fn with_activity(self, activity: Vec<String>) -> Self {
+        Self { activity, ..self }
+    }
+
+    pub fn with_images(self, images: Vec<Binary>) -> Self {
+        Self { images, ..self }
+    }
+
+    pub fn with_usage(self, usage: Vec<UsageRecord>, tool_calls: usize) -> Self {
+        Self {
+            usage,
+            tool_calls,
+            ..self
+        }
+    }
+}
+
 /// A request for user permission to execute a tool.
 #[derive(Clone, Debug)]
 pub struct PermissionRequest {
@@ -89,8 +151,26 @@ pub struct PermissionRequest {
 #[async_trait]
 pub trait WrappedTool {
     async fn call(&self, input: Value) -> Result<String, ToolError>;
+    /// Like `call`, but also returns any nested activity to display.
+    async fn call_with_output(&self, input: Value) -> Result<ToolOutput, ToolError> {
+        Ok(ToolOutput::new(self.call(input).await?))
+    }
+    /// Like `call_with_output`, for tools that spend from the caller's budget.
+    /// `budget` is what is left of it.
+    async fn call_with_budget(
+        &self,
+        input: Value,
+        _budget: BudgetConfig,
+    ) -> Result<ToolOutput, ToolError> {
+        self.call_with_output(input).await
+    }
     fn to_tool(&self) -> AITool;
     fn requires_permission(&self, session: &Session, input: &Value) -> Result<Permission, ToolError>;
+    /// Whether some calls need the user's approval. Sub-agents can't ask for
+    /// it, so they are never given these tools.
+    fn may_need_approval(&self) -> bool {
+        false
+    }
     fn describe_action(&self, input: &Value) -> String;
     async fn generate_preview(&self, input: &Value) -> Option<ToolPreview>;
 }
@@ -122,6 +202,10 @@ impl<T: Tool + Sync> WrappedTool for T {
         }
     }
 
+    fn may_need_approval(&self) -> bool {
+        T::may_need_approval()
+    }
+
     fn describe_action(&self, input: &Value) -> String {
         match serde_json::from_value::<T::Input>(input.clone()) {
             Ok(typed_input) => T::describe_action(&typed_input),
@@ -137,18 +221,51 @@ impl<T: Tool + Sync> WrappedTool for T {
     }
 }
 
+#[derive(Clone)]
 pub struct Toolset {
     order: Vec<AITool>, // order of tools matter for LLM performance
-    tools: HashMap<String, Box<dyn WrappedTool + Send + Sync + 'static>>,
+    tools: HashMap<String, Arc<dyn WrappedTool + Send + Sync + 'static>>,
 }
 
 impl Toolset {
     pub fn new(tools: Vec<Box<dyn WrappedTool + Send + Sync + 'static>>) -> Self {
         let order: Vec<_> = tools.iter().map(|x| x.to_tool()).collect();
-        let tools = tools.into_iter().map(|t| (t.to_tool().name, t)).collect();
+        let tools = tools
+            .into_iter()
+            .map(|t| (t.to_tool().name, Arc::from(t)))
+            .collect();
         Self { order, tools }
     }
 
+    /// Appends a tool after the existing ones.
+    pub fn with_tool(mut self, tool: Box<dyn WrappedTool + Send + Sync + 'static>) -> Self {
+        let info = tool.to_tool();
+        self.tools.insert(info.name.clone(), Arc::from(tool));
+        self.order.push(info);
+        self
+    }
+
+    /// Returns a toolset with only the named tools, keeping the original order,
+    /// along with the names that don't match any tool.
+    pub fn subset(&self, names: &[String]) -> (Self, Vec<String>) {
+        let order: Vec<_> = self
+            .order
+            .iter()
+            .filter(|t| names.contains(&t.name))
+            .cloned()
+            .collect();
+        let tools = order
+            .iter()
+            .filter_map(|t| Some((t.name.clone(), self.tools.get(&t.name)?.clone())))
+            .collect();
+        let unknown = names
+            .iter()
+            .filter(|name| !self.tools.contains_key(*name))
+            .cloned()
+            .collect();
+        (Self { order, tools }, unknown)
+    }
+
     pub fn list_tools(&self) -> Vec<AITool> {
         self.order.clone()
     }
@@ -163,6 +280,10 @@ impl Toolset {
         tool.requires_permission(session, input)
     }
 
+    pub fn may_need_approval(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|t| t.may_need_approval())
+    }
+
     pub fn describe_action(&self, name: &str, input: &Value) -> String {
         self.tools
             .get(name)
@@ -172,10 +293,49 @@ impl Toolset {
 
     pub async fn call(&self, name: String, input: Value) -> Result<String, ToolError> {
         let tool = self.tools.get(&name).ok_or(ToolError::ToolNotFound)?;
-        Ok(tool.call(input).await?)
+        tool.call(input).await
+    }
+
+    pub async fn call_with_output(&self, name: &str, input: Value) -> Result<ToolOutput, ToolError> {
+        let tool = self.tools.get(name).ok_or(ToolError::ToolNotFound)?;
+        tool.call_with_output(input).await
+    }
+
+    pub async fn call_with_budget(
+        &self,
+        name: &str,
+        input: Value,
+        budget: BudgetConfig,
+    ) -> Result<ToolOutput, ToolError> {
+        let tool = self.tools.get(name).ok_or(ToolError::ToolNotFound)?;
+        tool.call_with_budget(input, budget).await
     }
 
     pub async fn generate_preview(&self, name: &str, input: &Value) -> Option<ToolPreview> {
         self.tools.get(name)?.generate_preview(input).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::tools::builtin_tools;
+
+    #[test]
+    fn test_subset() {
+        let names = ["Grep", "Read", "Fetch"].map(String::from);
+        let (toolset, unknown) = Toolset::new(builtin_tools()).subset(&names);
+        let order: Vec<_> = toolset.list_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(order, ["Read", "Grep"]);
+        assert_eq!(unknown, ["Fetch"]);
+        assert!(!toolset.tools.contains_key("Bash"));
+    }
+
+    #[test]
+    fn test_may_need_approval() {
+        let toolset = Toolset::new(builtin_tools());
+        assert!(toolset.may_need_approval("Bash"));
+        assert!(!toolset.may_need_approval("Edit"));
+        assert!(!toolset.may_need_approval("Fetch"));
+    }
+}
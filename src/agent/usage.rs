@@ -0,0 +1,283 @@
+//! Token and cost accounting.
+//!
+//! Every model call is recorded in the session and appended to the monthly
+//! ledger in `~/.enchant/usage.jsonl`, one JSON object per line.
+
+use std::{
+    collections::BTreeMap,
+    ops::AddAssign,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use genai::chat::Usage;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+    agent::{
+        config::{check_directory, user_dir},
+        models::model_info::ModelPricing,
+    },
+    error::Error,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Input tokens that were neither read from nor written to the cache.
+    pub input: u64,
+    pub output: u64,
+    pub cache_read: u64,
+    pub cache_write: u64,
+}
+
+impl TokenUsage {
+    /// All tokens, cached ones included.
+    pub fn total(&self) -> u64 {
+        self.input + self.output + self.cache_read + self.cache_write
+    }
+
+    pub fn from_usage(usage: &Usage) -> Self {
+        let details = usage.prompt_tokens_details.as_ref();
+        let cache_read = details.and_then(|d| d.cached_tokens).unwrap_or(0) as u64;
+        let cache_write = details.and_then(|d| d.cache_creation_tokens).unwrap_or(0) as u64;
+        // Prompt tokens include the cached ones.
+        let prompt = usage.prompt_tokens.unwrap_or(0) as u64;
+        Self {
+            input: prompt.saturating_sub(cache_read + cache_write),
+            output: usage.completion_tokens.unwrap_or(0) as u64,
+            cache_read,
+            cache_write,
+        }
+    }
+
+    /// The cost in dollars, if the pricing is known.
+    pub fn cost(&self, pricing: Option<&ModelPricing>) -> Option<f64> {
+        let pricing = pricing?;
+        let cost = self.input as f64 * pricing.input
+            + self.output as f64 * pricing.output
+            + self.cache_read as f64 * pricing.cache_read.unwrap_or(pricing.input)
+            + self.cache_write as f64 * pricing.cache_write.unwrap_or(pricing.input);
+        Some(cost / 1e6)
+    }
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.input += other.input;
+        self.output += other.output;
+        self.cache_read += other.cache_read;
+        self.cache_write += other.cache_write;
+    }
+}
+
+/// The usage of a single model call.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// The user turn the call was made in, starting at 1.
+    pub turn: usize,
+    pub model: String,
+    pub tokens: TokenUsage,
+    /// Cost in dollars, if the model's pricing is known.
+    pub cost: Option<f64>,
+}
+
+/// Usage accumulated over a session.
+#[derive(Clone, Debug, Default)]
+pub struct UsageTracker {
+    pub records: Vec<UsageRecord>,
+    /// The current user turn.
+    pub turn: usize,
+}
+
+impl UsageTracker {
+    pub fn record(&mut self, model: &str, tokens: TokenUsage, cost: Option<f64>) -> UsageRecord {
+        let record = UsageRecord {
+            turn: self.turn,
+            model: model.to_string(),
+            tokens,
+            cost,
+        };
+        self.records.push(record.clone());
+        record
+    }
+
+    pub fn total_tokens(&self) -> TokenUsage {
+        let mut total = TokenUsage::default();
+        for record in &self.records {
+            total += record.tokens;
+        }
+        total
+    }
+
+    /// Total cost in dollars, counting calls with unknown pricing as free.
+    pub fn total_cost(&self) -> f64 {
+        self.records.iter().filter_map(|r| r.cost).sum()
+    }
+
+    /// Whether some calls were made to models without pricing data.
+    pub fn has_unpriced(&self) -> bool {
+        self.records.iter().any(|r| r.cost.is_none())
+    }
+
+    /// Tokens, cost and number of calls per model.
+    pub fn by_model(&self) -> BTreeMap<String, (TokenUsage, f64, usize)> {
+        let mut models: BTreeMap<String, (TokenUsage, f64, usize)> = BTreeMap::new();
+        for record in &self.records {
+            let entry = models.entry(record.model.clone()).or_default();
+            entry.0 += record.tokens;
+            entry.1 += record.cost.unwrap_or(0.0);
+            entry.2 += 1;
+        }
+        models
+    }
+
+    /// Tokens and cost per user turn.
+    pub fn by_turn(&self) -> BTreeMap<usize, (TokenUsage, f64)> {
+        let mut turns: BTreeMap<usize, (TokenUsage, f64)> = BTreeMap::new();
+        for record in &self.records {
+            let entry = turns.entry(record.turn).or_default();
+            entry.0 += record.tokens;
+            entry.1 += record.cost.unwrap_or(0.0);
+        }
+        turns
+    }
+}
+
+/// A line of `~/.enchant/usage.jsonl`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Unix timestamp in seconds.
+    pub timestamp: u64,
+    /// `YYYY-MM`, for monthly totals.
+    pub month: String,
+    pub repository: PathBuf,
+    pub session: String,
+    #[serde(flatten)]
+    pub record: UsageRecord,
+}
+
+pub fn ledger_path() -> PathBuf {
+    user_dir().join("usage.jsonl")
+}
+
+/// Appends a model call to the ledger.
+pub async fn append_ledger(
+    session: &str,
+    working_directory: &Path,
+    record: UsageRecord,
+) -> Result<(), Error> {
+    let timestamp = now();
+    let entry = LedgerEntry {
+        timestamp,
+        month: month_of(timestamp),
+        repository: repository_root(working_directory),
+        session: session.to_string(),
+        record,
+    };
+    check_directory(user_dir()).await?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(ledger_path())
+        .await?;
+    file.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())
+        .await?;
+    Ok(())
+}
+
+/// Total cost in dollars for the repository of `working_directory` this month.
+pub async fn monthly_cost(working_directory: &Path) -> Result<f64, Error> {
+    let content = match fs::read_to_string(ledger_path()).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0.0),
+        Err(e) => return Err(e.into()),
+    };
+    let month = month_of(now());
+    let repository = repository_root(working_directory);
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<LedgerEntry>(line).ok())
+        .filter(|e| e.month == month && e.repository == repository)
+        .filter_map(|e| e.record.cost)
+        .sum())
+}
+
+/// The root of the git repository containing `path`, or `path` itself.
+pub fn repository_root(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Formats a unix timestamp as `YYYY-MM` (UTC).
+fn month_of(timestamp: u64) -> String {
+    // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}-{month:02}")
+}
+
+/// Formats a dollar amount with more precision for small amounts.
+pub fn format_cost(cost: f64) -> String {
+    if cost < 0.01 {
+        format!("${cost:.4}")
+    } else {
+        format!("${cost:.2}")
+    }
+}
+
+/// Formats a token count as e.g. `950` or `12.3k`.
+pub fn format_tokens(tokens: u64) -> String {
+    if tokens < 1000 {
+        tokens.to_string()
+    } else {
+        format!("{:.1}k", tokens as f64 / 1e3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost() {
+        let tokens = TokenUsage {
+            input: 1_000_000,
+            output: 100_000,
+            cache_read: 2_000_000,
+            cache_write: 0,
+        };
+        let pricing = ModelPricing {
+            input: 3.0,
+            output: 15.0,
+            cache_read: Some(0.3),
+            cache_write: Some(3.75),
+        };
+        let cost = tokens.cost(Some(&pricing)).unwrap();
+        assert!((cost - 5.1).abs() < 1e-9);
+        assert_eq!(tokens.cost(None), None);
+    }
+
+    #[test]
+    fn test_month_of() {
+        assert_eq!(month_of(0), "1970-01");
+        assert_eq!(month_of(1_709_251_199), "2024-02"); // 2024-02-29T23:59:59Z
+        assert_eq!(month_of(1_709_251_200), "2024-03");
+    }
+}
@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::Session,
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+};
+
+pub struct Clear;
+
+#[async_trait]
+impl SlashCommand for Clear {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("clear").with_description("Start a new conversation")
+    }
+
+    async fn run(&self, session: &mut Session, _args: CommandArgs) -> Result<CommandOutcome, Error> {
+        session.clear();
+        Ok(CommandOutcome::None)
+    }
+}
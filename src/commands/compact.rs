@@ -0,0 +1,23 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::Session,
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+};
+
+pub struct Compact;
+
+#[async_trait]
+impl SlashCommand for Compact {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("compact")
+            .with_description("Replace the conversation with a summary, to free up context")
+            .with_rest_argument("instructions", false)
+    }
+
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error> {
+        session.compact(args.get("instructions")).await?;
+        Ok(CommandOutcome::Notice("Conversation compacted.".to_string()))
+    }
+}
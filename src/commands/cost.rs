@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::{
+        Session,
+        usage::{TokenUsage, format_cost, format_tokens, monthly_cost},
+    },
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+};
+
+pub struct Cost;
+
+#[async_trait]
+impl SlashCommand for Cost {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("cost").with_description("Show the token usage and cost of this session")
+    }
+
+    async fn run(
+        &self,
+        session: &mut Session,
+        _args: CommandArgs,
+    ) -> Result<CommandOutcome, Error> {
+        let usage = &session.usage;
+        let mut lines = vec![];
+        if usage.records.is_empty() {
+            lines.push("No tokens used yet.".to_string());
+        } else {
+            lines.push(format!(
+                "Session: {}  ({})",
+                format_cost(usage.total_cost()),
+                describe_tokens(&usage.total_tokens())
+            ));
+            if let Some(total) = session.total_tokens {
+                lines.push(format!("Context: {total} tokens"));
+            }
+
+            lines.push(String::new());
+            lines.push("By model:".to_string());
+            for (model, (tokens, cost, calls)) in usage.by_model() {
+                lines.push(format!(
+                    "  {model}  {}  {calls} calls  ({})",
+                    format_cost(cost),
+                    describe_tokens(&tokens)
+                ));
+            }
+
+            lines.push(String::new());
+            lines.push("By turn:".to_string());
+            for (turn, (tokens, cost)) in usage.by_turn() {
+                lines.push(format!(
+                    "  #{turn}  {}  ({})",
+                    format_cost(cost),
+                    describe_tokens(&tokens)
+                ));
+            }
+
+            if usage.has_unpriced() {
+                lines.push(String::new());
+                lines.push(
+                    "Some models have no pricing data and are counted as free. Add `pricing` to ~/.enchant/models.json."
+                        .to_string(),
+                );
+            }
+        }
+
+        let monthly = monthly_cost(&session.working_directory).await?;
+        lines.push(String::new());
+        lines.push(format!(
+            "This month in this repository: {}",
+            format_cost(monthly)
+        ));
+        Ok(CommandOutcome::Notice(lines.join("\n")))
+    }
+}
+
+fn describe_tokens(tokens: &TokenUsage) -> String {
+    let mut parts = vec![
+        format!("{} in", format_tokens(tokens.input)),
+        format!("{} out", format_tokens(tokens.output)),
+    ];
+    if tokens.cache_read > 0 {
+        parts.push(format!("{} cache read", format_tokens(tokens.cache_read)));
+    }
+    if tokens.cache_write > 0 {
+        parts.push(format!("{} cache write", format_tokens(tokens.cache_write)));
+    }
+    parts.join(" · ")
+}
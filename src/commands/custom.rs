@@ -0,0 +1,254 @@
+//! User-defined commands, loaded from `~/.enchant/commands/*.md` and
+//! `.enchant/commands/*.md`.
+//!
+//! The file body is a handlebars template that receives `{{arguments}}`.
+//! After rendering, `` !`command` `` is replaced by the command's output (subject
+//! to the Bash permission rules) and `@path` by the contents of the file. The
+//! arguments are only put in after the commands have run, so that they can't
+//! run commands of their own.
+
+use std::{collections::BTreeMap, path::Path};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    agent::{
+        Session,
+        config::user_dir,
+        prompt::render_prompt_with,
+        tools::{
+            bash::{Bash, BashInput},
+            permission::Permission,
+            tool::Tool,
+        },
+    },
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+    util::{
+        assert_working_directory,
+        front_matter::{parse_front_matter, read_markdown_dir, string_list},
+    },
+};
+
+/// Stands in for `{{arguments}}` until the shell commands have run.
+const ARGUMENTS_PLACEHOLDER: &str = "\u{0}arguments\u{0}";
+
+pub struct CustomCommand {
+    pub name: String,
+    pub description: String,
+    pub template: String,
+    /// Only these tools are offered to the model while the command runs.
+    pub tools: Option<Vec<String>>,
+    /// Model used while the command runs.
+    pub model: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CommandFrontMatter {
+    description: Option<String>,
+    argument_hint: Option<String>,
+    #[serde(default, deserialize_with = "string_list")]
+    tools: Option<Vec<String>>,
+    model: Option<String>,
+}
+
+/// Loads all custom commands. Project commands override user commands with the
+/// same name; files that fail to parse are reported as warnings.
+pub async fn load_custom_commands(
+    working_directory: impl AsRef<Path>,
+) -> (Vec<CustomCommand>, Vec<String>) {
+    let mut commands: Vec<CustomCommand> = vec![];
+    let mut warnings = vec![];
+
+    let dirs = [
+        (user_dir().join("commands"), "user"),
+        (
+            working_directory.as_ref().join(".enchant").join("commands"),
+            "project",
+        ),
+    ];
+    for (dir, scope) in dirs {
+        for (path, content) in read_markdown_dir(&dir).await {
+            let (meta, template) = match parse_front_matter::<CommandFrontMatter>(&content) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    warnings.push(format!("Invalid command {}: {e}", path.display()));
+                    continue;
+                }
+            };
+            let Some(name) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+
+            let mut description = meta.description.unwrap_or_default();
+            if let Some(hint) = meta.argument_hint {
+                description = format!("{hint} {description}").trim().to_string();
+            }
+            description = format!("{description} ({scope})").trim().to_string();
+
+            commands.retain(|c| c.name != name);
+            commands.push(CustomCommand {
+                name,
+                description,
+                template,
+                tools: meta.tools,
+                model: meta.model,
+            });
+        }
+    }
+
+    (commands, warnings)
+}
+
+impl CustomCommand {
+    async fn expand(&self, session: &Session, arguments: &str) -> Result<String, Error> {
+        let mut data = BTreeMap::new();
+        data.insert(
+            "arguments".to_string(),
+            Value::String(ARGUMENTS_PLACEHOLDER.to_string()),
+        );
+        let prompt = render_prompt_with(&self.template, data)?;
+
+        let prompt = expand_shell(session, &prompt).await?;
+        let prompt = prompt.replace(ARGUMENTS_PLACEHOLDER, arguments);
+        expand_files(&session.working_directory, &prompt).await
+    }
+}
+
+#[async_trait]
+impl SlashCommand for CustomCommand {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new(&self.name)
+            .with_description(&self.description)
+            .with_rest_argument("arguments", false)
+    }
+
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error> {
+        let prompt = self.expand(session, &args.raw).await?;
+        session.overrides.model = self.model.clone();
+        session.overrides.tools = self.tools.clone();
+        Ok(CommandOutcome::Prompt(prompt))
+    }
+}
+
+/// Replaces each `` !`command` `` with the command's output.
+async fn expand_shell(session: &Session, text: &str) -> Result<String, Error> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("!`") {
+        let Some(len) = rest[start + 2..].find('`') else {
+            break;
+        };
+        let command = &rest[start + 2..start + 2 + len];
+        out.push_str(&rest[..start]);
+        if command.contains(ARGUMENTS_PLACEHOLDER) {
+            return Err(Error::CommandError(
+                "`{{arguments}}` can't be used in a !`command`".to_string(),
+            ));
+        }
+
+        let input = BashInput {
+            command: command.to_string(),
+        };
+        if Bash::requires_permission(session, &input)? != Permission::Implicit {
+            return Err(Error::CommandError(format!(
+                "`{command}` is not allowed by the Bash permissions (permissions.bash.allow)"
+            )));
+        }
+        out.push_str(Bash::execute(input).await?.trim_end());
+
+        rest = &rest[start + 2 + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Replaces each `@path` naming a file in the working directory with its contents.
+async fn expand_files(working_directory: &Path, text: &str) -> Result<String, Error> {
+    let mut out = String::new();
+    for (i, word) in text.split_inclusive(char::is_whitespace).enumerate() {
+        let trimmed = word.trim_end();
+        let mention = trimmed
+            .strip_prefix('@')
+            .map(|p| p.trim_end_matches(['.', ',', ';', ')', '!', '?']))
+            .filter(|p| !p.is_empty() && !p.contains(':'));
+        // Only at the start of the text or after whitespace, so emails are left alone.
+        let at_boundary = i == 0 || out.ends_with(char::is_whitespace);
+
+        let path = match mention {
+            Some(path) if at_boundary => working_directory.join(path),
+            _ => {
+                out.push_str(word);
+                continue;
+            }
+        };
+        if !path.is_file() {
+            out.push_str(word);
+            continue;
+        }
+        assert_working_directory(path.canonicalize()?)?;
+
+        let content = tokio::fs::read_to_string(&path).await?;
+        out.push_str(&format!(
+            "{}\n\n```\n{}\n```\n{}",
+            trimmed,
+            content.trim_end(),
+            &word[trimmed.len()..]
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::tools::tool::Toolset;
+
+    #[test]
+    fn test_front_matter() {
+        let content = "---\ndescription: Review a file\nargument-hint: <path>\ntools: Read, Grep\n---\nReview @{{arguments}}\n";
+        let (meta, template) = parse_front_matter::<CommandFrontMatter>(content).unwrap();
+        assert_eq!(meta.description.as_deref(), Some("Review a file"));
+        assert_eq!(meta.argument_hint.as_deref(), Some("<path>"));
+        assert_eq!(
+            meta.tools,
+            Some(vec!["Read".to_string(), "Grep".to_string()])
+        );
+        assert_eq!(meta.model, None);
+        assert_eq!(template.trim(), "Review @{{arguments}}");
+    }
+
+    #[tokio::test]
+    async fn test_expand_arguments() {
+        let session = Session::new_subagent(
+            Default::default(),
+            Default::default(),
+            "m".to_string(),
+            Toolset::new(vec![]),
+            String::new(),
+        )
+        .unwrap();
+        let command = |template: &str| CustomCommand {
+            name: "say".to_string(),
+            description: String::new(),
+            template: template.to_string(),
+            tools: None,
+            model: None,
+        };
+
+        let prompt = command("Say {{arguments}}")
+            .expand(&session, "!`echo hi`")
+            .await
+            .unwrap();
+        assert_eq!(prompt, "Say !`echo hi`");
+
+        let err = command("Say !`echo {{arguments}}`")
+            .expand(&session, "hi")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("can't be used in a !`command`"));
+    }
+}
@@ -0,0 +1,26 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::Session,
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+};
+
+pub struct Help;
+
+#[async_trait]
+impl SlashCommand for Help {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("help").with_description("List the available commands")
+    }
+
+    async fn run(&self, session: &mut Session, _args: CommandArgs) -> Result<CommandOutcome, Error> {
+        let commands = session.commands.list();
+        let width = commands.iter().map(|c| c.usage().len()).max().unwrap_or(0);
+        let lines: Vec<String> = commands
+            .iter()
+            .map(|c| format!("{:width$}  {}", c.usage(), c.description))
+            .collect();
+        Ok(CommandOutcome::Notice(lines.join("\n")))
+    }
+}
@@ -0,0 +1,22 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::Session,
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+};
+
+pub struct Init;
+
+#[async_trait]
+impl SlashCommand for Init {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("init").with_description("Create an ENCHANT.md file for this project")
+    }
+
+    async fn run(&self, _session: &mut Session, _args: CommandArgs) -> Result<CommandOutcome, Error> {
+        Ok(CommandOutcome::Prompt(
+            include_str!("../../prompts/INIT.md").to_string(),
+        ))
+    }
+}
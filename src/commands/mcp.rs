@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use iocraft::Color;
+
+use crate::{
+    agent::{Session, mcp::McpServerStatus},
+    commands::{CommandArgs, CommandInfo, CommandOutcome, Picker, PickerOption, SlashCommand},
+    error::Error,
+};
+
+pub struct Mcp;
+
+#[async_trait]
+impl SlashCommand for Mcp {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("mcp")
+            .with_description("Show the MCP servers, or reconnect one")
+            .with_argument("server", false)
+    }
+
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error> {
+        if session.mcp.servers().next().is_none() {
+            return Ok(CommandOutcome::Notice(
+                "No MCP servers configured.".to_string(),
+            ));
+        }
+
+        match args.get("server") {
+            Some(name) => reconnect(session, name).await,
+            None => Ok(CommandOutcome::Pick(server_picker(session))),
+        }
+    }
+}
+
+/// Restarts a server and replaces its tools with the ones it lists now.
+async fn reconnect(session: &mut Session, name: &str) -> Result<CommandOutcome, Error> {
+    let server = session
+        .mcp
+        .server(name)
+        .cloned()
+        .ok_or_else(|| Error::CommandError(format!("Unknown MCP server '{name}'")))?;
+    let defs = server.connect().await?;
+    let count = session.replace_mcp_tools(&server, defs);
+
+    let mut notice = format!("Reconnected {name} ({count} tools)");
+    if let Ok(client) = server.client()
+        && let Some(warning) = client.warning()
+    {
+        notice.push_str(&format!("\n{warning}"));
+    }
+    Ok(CommandOutcome::Notice(notice))
+}
+
+fn server_picker(session: &Session) -> Picker {
+    let tools = session.tools.list_tools();
+    let options = session
+        .mcp
+        .servers()
+        .map(|server| {
+            let prefix = format!("mcp.{}.", server.name());
+            let count = tools.iter().filter(|t| t.name.starts_with(&prefix)).count();
+            let (description, color) = match server.status() {
+                McpServerStatus::Connected(_) => {
+                    (format!("connected, {count} tools"), Color::Green)
+                }
+                McpServerStatus::Failed(reason) => (format!("failed: {reason}"), Color::Red),
+                McpServerStatus::Disabled => ("disabled".to_string(), Color::DarkGrey),
+            };
+            PickerOption {
+                value: server.name().to_string(),
+                label: server.name().to_string(),
+                description,
+                color: Some(color),
+            }
+        })
+        .collect();
+
+    Picker {
+        title: "MCP servers (select one to reconnect it)".to_string(),
+        command: "mcp".to_string(),
+        options,
+        selected: 0,
+    }
+}
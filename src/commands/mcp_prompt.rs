@@ -0,0 +1,133 @@
+//! Prompts published by MCP servers, run as `/mcp__<server>__<prompt>`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use genai::chat::ChatMessage;
+use rmcp::model::{
+    Prompt, PromptMessage, PromptMessageContent, PromptMessageRole, ResourceContents,
+};
+
+use crate::{
+    agent::{
+        Session,
+        mcp::{McpServer, McpServers},
+    },
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+};
+
+pub struct McpPromptCommand {
+    server: Arc<McpServer>,
+    prompt: Prompt,
+}
+
+impl McpPromptCommand {
+    pub fn name(&self) -> String {
+        format!("mcp__{}__{}", self.server.name(), self.prompt.name)
+    }
+}
+
+#[async_trait]
+impl SlashCommand for McpPromptCommand {
+    fn info(&self) -> CommandInfo {
+        let description = self
+            .prompt
+            .description
+            .clone()
+            .unwrap_or(format!("Prompt from the {} MCP server", self.server.name()));
+        let mut info = CommandInfo::new(&self.name()).with_description(&description);
+        for argument in self.prompt.arguments.iter().flatten() {
+            info = info.with_argument(&argument.name, argument.required.unwrap_or(false));
+        }
+        info
+    }
+
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error> {
+        let arguments: HashMap<String, String> = self
+            .prompt
+            .arguments
+            .iter()
+            .flatten()
+            .filter_map(|a| Some((a.name.clone(), args.get(&a.name)?.to_string())))
+            .collect();
+        let result = self
+            .server
+            .live_client()
+            .await?
+            .get_prompt(&self.prompt.name, arguments)
+            .await?;
+
+        let mut messages = result.messages;
+        // A final user message is sent as the prompt, so the model answers it.
+        let prompt = match messages.last() {
+            Some(PromptMessage {
+                role: PromptMessageRole::User,
+                content,
+            }) => {
+                let prompt = content_text(content);
+                messages.pop();
+                Some(prompt)
+            }
+            _ => None,
+        };
+        for message in &messages {
+            let text = content_text(&message.content);
+            session.messages.push(match message.role {
+                PromptMessageRole::User => ChatMessage::user(text),
+                PromptMessageRole::Assistant => ChatMessage::assistant(text),
+            });
+        }
+
+        Ok(match prompt {
+            Some(prompt) => CommandOutcome::Prompt(prompt),
+            None => CommandOutcome::None,
+        })
+    }
+}
+
+/// The content of a prompt message as text. Images and binary resources are described.
+fn content_text(content: &PromptMessageContent) -> String {
+    match content {
+        PromptMessageContent::Text { text } => text.clone(),
+        PromptMessageContent::Image { image } => format!("[image: {}]", image.mime_type),
+        PromptMessageContent::Resource { resource } => match &resource.resource {
+            ResourceContents::TextResourceContents { uri, text, .. } => {
+                format!("<resource uri=\"{uri}\">\n{text}\n</resource>")
+            }
+            ResourceContents::BlobResourceContents { uri, mime_type, .. } => {
+                format!(
+                    "[resource: {uri}, {}]",
+                    mime_type.as_deref().unwrap_or("binary")
+                )
+            }
+        },
+        PromptMessageContent::ResourceLink { link } => format!("[resource link: {}]", link.uri),
+    }
+}
+
+/// The prompts of every server that publishes some. Servers whose prompts
+/// could not be listed are reported as warnings.
+pub async fn load_mcp_prompts(servers: &McpServers) -> (Vec<McpPromptCommand>, Vec<String>) {
+    let mut commands = vec![];
+    let mut warnings = vec![];
+    for server in servers.servers() {
+        let Ok(client) = server.client() else {
+            continue;
+        };
+        if !client.supports_prompts() {
+            continue;
+        }
+        match client.list_prompts().await {
+            Ok(prompts) => commands.extend(prompts.into_iter().map(|prompt| McpPromptCommand {
+                server: server.clone(),
+                prompt,
+            })),
+            Err(err) => warnings.push(format!(
+                "Failed to list the prompts of {}: {err}",
+                server.name()
+            )),
+        }
+    }
+    (commands, warnings)
+}
@@ -1 +1,267 @@
+//! Slash commands, run client-side from the input box (e.g. `/clear`).
 
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use iocraft::Color;
+
+use crate::{agent::Session, error::Error};
+
+pub mod clear;
+pub mod compact;
+pub mod cost;
+pub mod custom;
+pub mod help;
+pub mod init;
+pub mod mcp;
+pub mod mcp_prompt;
+pub mod model;
+pub mod permissions;
+pub mod resume;
+pub mod think;
+
+/// What the terminal should do after a command has run.
+pub enum CommandOutcome {
+    /// Nothing more to do.
+    None,
+    /// Show a notice in the transcript.
+    Notice(String),
+    /// Send a prompt to the model, as if the user had typed it.
+    Prompt(String),
+    /// Let the user pick an option, then run the command again with it.
+    Pick(Picker),
+}
+
+/// A list of options to choose from. The chosen value is run as `/<command> <value>`.
+#[derive(Clone, Debug)]
+pub struct Picker {
+    pub title: String,
+    pub command: String,
+    pub options: Vec<PickerOption>,
+    /// Index of the option selected initially.
+    pub selected: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct PickerOption {
+    pub value: String,
+    pub label: String,
+    pub description: String,
+    pub color: Option<Color>,
+}
+
+pub struct ArgumentInfo {
+    pub name: String,
+    pub required: bool,
+    /// Takes the rest of the input, spaces included.
+    pub rest: bool,
+}
+
+pub struct CommandInfo {
+    pub name: String,
+    pub description: String,
+    pub arguments: Vec<ArgumentInfo>,
+}
+
+impl CommandInfo {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: String::new(),
+            arguments: vec![],
+        }
+    }
+
+    pub fn with_description(self, description: &str) -> Self {
+        Self {
+            description: description.to_string(),
+            ..self
+        }
+    }
+
+    pub fn with_argument(mut self, name: &str, required: bool) -> Self {
+        self.arguments.push(ArgumentInfo {
+            name: name.to_string(),
+            required,
+            rest: false,
+        });
+        self
+    }
+
+    /// A final argument that takes the rest of the input verbatim.
+    pub fn with_rest_argument(mut self, name: &str, required: bool) -> Self {
+        self.arguments.push(ArgumentInfo {
+            name: name.to_string(),
+            required,
+            rest: true,
+        });
+        self
+    }
+
+    /// Usage line, e.g. `/model [name]`.
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+        for arg in &self.arguments {
+            let dots = if arg.rest { "..." } else { "" };
+            if arg.required {
+                usage.push_str(&format!(" <{}{dots}>", arg.name));
+            } else {
+                usage.push_str(&format!(" [{}{dots}]", arg.name));
+            }
+        }
+        usage
+    }
+
+    /// Parses the text after the command name against the declared arguments.
+    pub fn parse_args(&self, input: &str) -> Result<CommandArgs, Error> {
+        let usage_error = || Error::CommandError(format!("Usage: {}", self.usage()));
+
+        let mut values = HashMap::new();
+        let mut remaining = input.trim();
+        for arg in &self.arguments {
+            if arg.rest {
+                if !remaining.is_empty() {
+                    values.insert(arg.name.clone(), remaining.to_string());
+                }
+                remaining = "";
+                continue;
+            }
+            if let Some((word, rest)) = next_word(remaining).map_err(|_| usage_error())? {
+                values.insert(arg.name.clone(), word);
+                remaining = rest;
+            }
+        }
+
+        let missing = self
+            .arguments
+            .iter()
+            .any(|arg| arg.required && !values.contains_key(&arg.name));
+        if missing || !remaining.is_empty() {
+            return Err(usage_error());
+        }
+
+        Ok(CommandArgs {
+            raw: input.trim().to_string(),
+            values,
+        })
+    }
+}
+
+/// Arguments of a command invocation.
+pub struct CommandArgs {
+    /// Everything after the command name.
+    pub raw: String,
+    values: HashMap<String, String>,
+}
+
+impl CommandArgs {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|x| x.as_str())
+    }
+}
+
+/// Splits the next shell-like word off the input, honouring single and double quotes.
+fn next_word(input: &str) -> Result<Option<(String, &str)>, Error> {
+    let input = input.trim_start();
+    if input.is_empty() {
+        return Ok(None);
+    }
+
+    let mut word = String::new();
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, c) if c.is_whitespace() => return Ok(Some((word, input[i..].trim_start()))),
+            (_, c) => word.push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(Error::CommandError("Unterminated quote".to_string()));
+    }
+    Ok(Some((word, "")))
+}
+
+#[async_trait]
+pub trait SlashCommand: Send + Sync {
+    fn info(&self) -> CommandInfo;
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error>;
+}
+
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<Arc<dyn SlashCommand>>,
+}
+
+impl CommandRegistry {
+    /// The built-in commands.
+    pub fn builtin() -> Self {
+        Self::default()
+            .with_command(Arc::new(help::Help))
+            .with_command(Arc::new(clear::Clear))
+            .with_command(Arc::new(compact::Compact))
+            .with_command(Arc::new(model::Model))
+            .with_command(Arc::new(think::Think))
+            .with_command(Arc::new(cost::Cost))
+            .with_command(Arc::new(permissions::Permissions))
+            .with_command(Arc::new(mcp::Mcp))
+            .with_command(Arc::new(resume::Resume))
+            .with_command(Arc::new(init::Init))
+    }
+
+    /// Adds a command, replacing any existing command with the same name.
+    pub fn with_command(mut self, command: Arc<dyn SlashCommand>) -> Self {
+        let name = command.info().name;
+        self.commands.retain(|c| c.info().name != name);
+        self.commands.push(command);
+        self
+    }
+
+    pub fn list(&self) -> Vec<CommandInfo> {
+        self.commands.iter().map(|c| c.info()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn SlashCommand>> {
+        self.commands.iter().find(|c| c.info().name == name)
+    }
+
+    /// Runs a command line such as `/model claude-sonnet-4-5`.
+    pub async fn execute(&self, session: &mut Session, input: &str) -> Result<CommandOutcome, Error> {
+        let input = input.trim().strip_prefix('/').unwrap_or(input);
+        let (name, rest) = input
+            .split_once(char::is_whitespace)
+            .unwrap_or((input, ""));
+
+        let command = self.get(name).ok_or_else(|| {
+                Error::CommandError(format!("Unknown command /{name}. Type /help for a list."))
+            })?;
+        let args = command.info().parse_args(rest)?;
+        command.run(session, args).await
+    }
+}
+
+/// Whether the input should be run as a slash command rather than sent to the model.
+pub fn is_command(input: &str) -> bool {
+    input.starts_with('/') && !input.starts_with("//")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let info = CommandInfo::new("permissions")
+            .with_argument("action", false)
+            .with_rest_argument("rule", false);
+        let args = info.parse_args(r#"allow cargo test *"#).unwrap();
+        assert_eq!(args.get("action"), Some("allow"));
+        assert_eq!(args.get("rule"), Some("cargo test *"));
+
+        let info = CommandInfo::new("resume").with_argument("id", true);
+        assert!(info.parse_args("").is_err());
+        assert!(info.parse_args("a b").is_err());
+        assert_eq!(info.parse_args(r#""a b""#).unwrap().get("id"), Some("a b"));
+    }
+}
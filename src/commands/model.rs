@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::Session,
+    commands::{CommandArgs, CommandInfo, CommandOutcome, Picker, PickerOption, SlashCommand},
+    error::Error,
+};
+
+pub struct Model;
+
+#[async_trait]
+impl SlashCommand for Model {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("model")
+            .with_description("Pick or change the model")
+            .with_argument("name", false)
+    }
+
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error> {
+        let Some(name) = args.get("name") else {
+            return Ok(CommandOutcome::Pick(model_picker(session)));
+        };
+
+        // History is kept, so the new model picks up the conversation where it left off.
+        session.model = session.models().resolve(name);
+        session.fallback = None;
+        let display = session.model_info().name.unwrap_or(name.to_string());
+        Ok(CommandOutcome::Notice(format!("Switched to {display}")))
+    }
+}
+
+fn model_picker(session: &Session) -> Picker {
+    let mut models = session.models().list();
+    if !models.iter().any(|(id, _)| *id == session.model) {
+        models.insert(0, (session.model.clone(), session.model_info()));
+    }
+
+    let options = models
+        .into_iter()
+        .map(|(id, info)| PickerOption {
+            label: info.name.clone().unwrap_or(id.clone()),
+            description: match info.max_context {
+                Some(max) => format!("{id} · {}k context", max / 1000),
+                None => id.clone(),
+            },
+            color: Some(info.get_color()),
+            value: id,
+        })
+        .collect::<Vec<_>>();
+    let selected = options
+        .iter()
+        .position(|o| o.value == session.model)
+        .unwrap_or_default();
+
+    Picker {
+        title: "Select a model".to_string(),
+        command: "model".to_string(),
+        options,
+        selected,
+    }
+}
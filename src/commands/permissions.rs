@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::Session,
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+};
+
+pub struct Permissions;
+
+#[async_trait]
+impl SlashCommand for Permissions {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("permissions")
+            .with_description("Show the Bash allowlist, or allow a command for this session")
+            .with_argument("action", false)
+            .with_rest_argument("rule", false)
+    }
+
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error> {
+        match (args.get("action"), args.get("rule")) {
+            (None, _) => {
+                let allow = &session.config().permissions.bash.allow;
+                if allow.is_empty() {
+                    return Ok(CommandOutcome::Notice(
+                        "No Bash commands are allowed beyond the built-in safe ones.".to_string(),
+                    ));
+                }
+                let mut lines = vec!["Allowed Bash commands:".to_string()];
+                lines.extend(allow.iter().map(|rule| format!("  {rule}")));
+                Ok(CommandOutcome::Notice(lines.join("\n")))
+            }
+            (Some("allow"), Some(rule)) => {
+                session
+                    .config_mut()
+                    .permissions
+                    .bash
+                    .allow
+                    .push(rule.to_string());
+                Ok(CommandOutcome::Notice(format!(
+                    "Allowed `{rule}` for this session."
+                )))
+            }
+            _ => Err(Error::CommandError(
+                "Usage: /permissions [allow <rule>]".to_string(),
+            )),
+        }
+    }
+}
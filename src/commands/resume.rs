@@ -0,0 +1,50 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::{
+        Session,
+        history::{format_age, list_sessions, load_session},
+    },
+    commands::{CommandArgs, CommandInfo, CommandOutcome, SlashCommand},
+    error::Error,
+};
+
+/// How many sessions `/resume` lists.
+const LIST_LIMIT: usize = 10;
+
+pub struct Resume;
+
+#[async_trait]
+impl SlashCommand for Resume {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("resume")
+            .with_description("List recent conversations, or resume one by id")
+            .with_argument("id", false)
+    }
+
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error> {
+        if let Some(id) = args.get("id") {
+            let saved = load_session(id).await?;
+            let title = saved.title();
+            session.restore(saved);
+            return Ok(CommandOutcome::Notice(format!("Resumed: {title}")));
+        }
+
+        let sessions = list_sessions(&session.working_directory).await?;
+        if sessions.is_empty() {
+            return Ok(CommandOutcome::Notice(
+                "No saved conversations for this directory.".to_string(),
+            ));
+        }
+        let mut lines = vec!["Recent conversations (resume with /resume <id>):".to_string()];
+        for saved in sessions.iter().take(LIST_LIMIT) {
+            lines.push(format!(
+                "  {}  {:>8}  {}",
+                saved.id,
+                format_age(saved.updated_at),
+                saved.title()
+            ));
+        }
+        Ok(CommandOutcome::Notice(lines.join("\n")))
+    }
+}
@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+
+use crate::{
+    agent::{
+        Session,
+        reasoning::{Reasoning, ReasoningLevel},
+    },
+    commands::{CommandArgs, CommandInfo, CommandOutcome, Picker, PickerOption, SlashCommand},
+    error::Error,
+};
+
+pub struct Think;
+
+#[async_trait]
+impl SlashCommand for Think {
+    fn info(&self) -> CommandInfo {
+        CommandInfo::new("think")
+            .with_description("Set the reasoning effort or thinking budget")
+            .with_argument("effort", false)
+    }
+
+    async fn run(&self, session: &mut Session, args: CommandArgs) -> Result<CommandOutcome, Error> {
+        let info = session.model_info();
+        let name = info.name.clone().unwrap_or(session.model.clone());
+        if info.reasoning == Some(false) {
+            return Err(Error::CommandError(format!(
+                "{name} does not support reasoning"
+            )));
+        }
+
+        let Some(effort) = args.get("effort") else {
+            return Ok(CommandOutcome::Pick(effort_picker(session)));
+        };
+        let reasoning = Reasoning::parse(effort).ok_or_else(|| {
+            Error::CommandError(format!(
+                "Unknown effort {effort}. Use off, low, medium, high or a number of tokens."
+            ))
+        })?;
+
+        session.reasoning_effort = Some(reasoning);
+        Ok(CommandOutcome::Notice(if reasoning.is_on() {
+            format!("Reasoning effort for {name}: {reasoning}")
+        } else {
+            format!("Reasoning off for {name}")
+        }))
+    }
+}
+
+fn effort_picker(session: &Session) -> Picker {
+    let current = session.current_reasoning().unwrap_or_default();
+    let levels = [
+        (ReasoningLevel::Off, "Answer without thinking first"),
+        (ReasoningLevel::Low, "Think briefly"),
+        (ReasoningLevel::Medium, "Think before answering"),
+        (ReasoningLevel::High, "Think at length, for hard problems"),
+    ];
+
+    let mut options: Vec<PickerOption> = levels
+        .into_iter()
+        .map(|(level, description)| {
+            let value = Reasoning::Level(level).to_string();
+            PickerOption {
+                label: value.clone(),
+                value,
+                description: description.to_string(),
+                color: None,
+            }
+        })
+        .collect();
+    if let Reasoning::Budget(budget) = current {
+        options.push(PickerOption {
+            value: budget.to_string(),
+            label: current.to_string(),
+            description: "Thinking budget".to_string(),
+            color: None,
+        });
+    }
+    let selected = options
+        .iter()
+        .position(|o| Reasoning::parse(&o.value) == Some(current))
+        .unwrap_or_default();
+
+    Picker {
+        title: "Select the reasoning effort".to_string(),
+        command: "think".to_string(),
+        options,
+        selected,
+    }
+}
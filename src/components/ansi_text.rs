@@ -120,26 +120,26 @@ fn parse_ansi_to_runs(input: &str) -> (Vec<(usize, Vec<StyledRun>)>, usize, usiz
                     }
                 }
                 // Consume the final character (usually 'm')
-                if let Some(cmd) = chars.next() {
-                    if cmd == 'm' {
-                        let new_state = parse_sgr(&params, state);
-                        // If style changed, finish current run and start new one
-                        if new_state != state {
-                            if !current_run.text.is_empty() {
-                                current_line_runs.push(current_run);
-                                current_run = StyledRun {
-                                    x,
-                                    text: String::new(),
-                                    char_count: 0,
-                                    fg: new_state.fg,
-                                    bg: new_state.bg,
-                                };
-                            } else {
-                                current_run.fg = new_state.fg;
-                                current_run.bg = new_state.bg;
-                            }
-                            state = new_state;
+                if let Some(cmd) = chars.next()
+                    && cmd == 'm'
+                {
+                    let new_state = parse_sgr(&params, state);
+                    // If style changed, finish current run and start new one
+                    if new_state != state {
+                        if !current_run.text.is_empty() {
+                            current_line_runs.push(current_run);
+                            current_run = StyledRun {
+                                x,
+                                text: String::new(),
+                                char_count: 0,
+                                fg: new_state.fg,
+                                bg: new_state.bg,
+                            };
+                        } else {
+                            current_run.fg = new_state.fg;
+                            current_run.bg = new_state.bg;
                         }
+                        state = new_state;
                     }
                 }
             }
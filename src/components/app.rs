@@ -1,16 +1,25 @@
+use std::time::Instant;
+
 use iocraft::prelude::*;
 
 use crate::{
     agent::{
         Session, ThinkResult,
-        config::{ConfigState, load_config},
-        models::model_info::get_model_info,
+        budget::BudgetLimit,
+        config::{Config, ConfigState, load_config},
+        history::save_session,
+        mcp::resources::expand_resource_mentions,
+        notice::NoticeLevel,
+        retry::retry_delay,
         tools::tool::PermissionRequest,
     },
+    commands::{CommandOutcome, Picker, PickerOption, is_command},
     components::{
-        AnsiText, COLOR_PRIMARY, InputBox, PermissionChoice, PermissionPrompt, StatusLine,
-        ThinkingIndicator, message::Message,
+        AnsiText, COLOR_PRIMARY, Completion, InputBox, PermissionChoice, PermissionPrompt,
+        PickerPrompt, RetryStatus,
+        StatusLine, ThinkingIndicator, message::Message, notice_message::NoticeMessage,
     },
+    util::env::References,
 };
 
 /// UI state for the app.
@@ -19,20 +28,33 @@ enum AppState {
     #[default]
     Idle,
     Thinking,
+    /// Waiting to retry a model call that failed with a transient error.
+    Retrying(RetryStatus),
     AwaitingPermission(Vec<PermissionRequest>),
+    Picking(Picker),
+    BudgetReached(BudgetLimit),
+}
+
+#[derive(Default, Props)]
+pub struct AppProps {
+    /// Settings from the command line.
+    pub overrides: Config,
 }
 
 #[component]
-pub fn App(mut hooks: Hooks) -> impl Into<AnyElement<'static>> {
+pub fn App(mut hooks: Hooks, props: &AppProps) -> impl Into<AnyElement<'static>> {
     let mut config = hooks.use_state(|| None::<ConfigState>);
+    let mut error = hooks.use_state(|| None::<String>);
 
     hooks.use_future({
+        let overrides = props.overrides.clone();
         async move {
-            match load_config().await {
-                Ok(loaded_config) => {
+            match load_config(References::Resolve).await {
+                Ok(mut loaded_config) => {
+                    loaded_config.overrides = overrides;
                     config.set(Some(loaded_config));
                 }
-                Err(err) => panic!("{:?}", err),
+                Err(err) => error.set(Some(format!("Failed to load the config: {err}"))),
             }
         }
     });
@@ -46,10 +68,99 @@ pub fn App(mut hooks: Hooks) -> impl Into<AnyElement<'static>> {
                     None
                 }
             )
+            #(error.read().clone().map(|error| element! {
+                Text(content: error, color: Color::Red)
+            }))
         }
     }
 }
 
+/// Runs think steps until the agent is done or needs permission.
+async fn run_think_loop(mut session: State<Session>, mut app_state: State<AppState>) {
+    let mut attempt = 0;
+    loop {
+        let mut sess = (*session.read()).clone();
+        let result = sess.think_step().await;
+        *session.write() = sess;
+
+        let result = match result {
+            Ok(result) => {
+                attempt = 0;
+                result
+            }
+            Err(err) => {
+                attempt += 1;
+                if let Some(delay) = retry_delay(&err, attempt) {
+                    let reason = err.to_string();
+                    app_state.set(AppState::Retrying(RetryStatus {
+                        reason: reason.lines().next().unwrap_or_default().to_string(),
+                        attempt,
+                        until: Instant::now() + delay,
+                    }));
+                    tokio::time::sleep(delay).await;
+                    app_state.set(AppState::Thinking);
+                    continue;
+                }
+                session.write().notify(NoticeLevel::Error, err.to_string());
+                app_state.set(AppState::Idle);
+                break;
+            }
+        };
+        match result {
+            ThinkResult::Done => {
+                app_state.set(AppState::Idle);
+                break;
+            }
+            ThinkResult::NeedsPermission(requests) => {
+                app_state.set(AppState::AwaitingPermission(requests));
+                break;
+            }
+            ThinkResult::Continue => {
+                // Keep looping
+            }
+            ThinkResult::BudgetReached(limit) => {
+                app_state.set(AppState::BudgetReached(limit));
+                break;
+            }
+        }
+    }
+
+    let sess = (*session.read()).clone();
+    if let Err(err) = save_session(&sess).await {
+        session
+            .write()
+            .notify(NoticeLevel::Warning, format!("Failed to save session: {err}"));
+    }
+}
+
+/// Runs a slash command. Returns the prompt to send to the model, if any;
+/// otherwise the app is moved out of the thinking state.
+async fn run_command(
+    mut session: State<Session>,
+    mut app_state: State<AppState>,
+    input: &str,
+) -> Option<String> {
+    let mut sess = (*session.read()).clone();
+    let commands = sess.commands.clone();
+    let outcome = commands.execute(&mut sess, input).await;
+    *session.write() = sess;
+
+    let mut next_state = AppState::Idle;
+    match outcome {
+        Ok(CommandOutcome::None) => {}
+        Ok(CommandOutcome::Notice(text)) => {
+            session.write().notify(NoticeLevel::Info, text);
+        }
+        Ok(CommandOutcome::Prompt(prompt)) => return Some(prompt),
+        Ok(CommandOutcome::Pick(picker)) => next_state = AppState::Picking(picker),
+        Err(err) => {
+            session.write().notify(NoticeLevel::Error, err.to_string());
+        }
+    }
+    app_state.set(next_state);
+    None
+}
+
 #[derive(Default, Props)]
 pub struct TerminalProps {
     pub config: Option<ConfigState>,
@@ -60,60 +171,108 @@ pub fn Terminal(mut hooks: Hooks, props: &TerminalProps) -> impl Into<AnyElement
     let mut input = hooks.use_state(|| "".to_string());
     let mut session = hooks.use_state(|| Session::new(&props.config.clone().unwrap()));
     let mut app_state = hooks.use_state(AppState::default);
+    let mut expand_reasoning = hooks.use_state(|| false);
 
-    // Handler for continuing the think loop after permission is resolved
-    let continue_thinking = hooks.use_async_handler({
-        move |_: ()| async move {
-            loop {
-                let mut sess = (*session.read()).clone();
-                let result = sess.think_step().await.unwrap();
-                *session.write() = sess;
-
-                match result {
-                    ThinkResult::Done => {
-                        app_state.set(AppState::Idle);
-                        break;
-                    }
-                    ThinkResult::NeedsPermission(requests) => {
-                        app_state.set(AppState::AwaitingPermission(requests));
-                        break;
-                    }
-                    ThinkResult::Continue => {
-                        // Keep looping
+    // Ctrl+T turns reasoning on and off, Ctrl+O expands the reasoning of every message
+    hooks.use_terminal_events(move |event| match event {
+        TerminalEvent::Key(KeyEvent {
+            code, modifiers, kind, ..
+        }) if kind != KeyEventKind::Release && modifiers.contains(KeyModifiers::CONTROL) => {
+            match code {
+                KeyCode::Char('t') => {
+                    let mut sess = session.write();
+                    if sess.model_info().reasoning == Some(false) {
+                        return;
                     }
+                    let reasoning = sess.toggle_reasoning();
+                    let text = if reasoning.is_on() {
+                        format!("Reasoning effort: {reasoning}")
+                    } else {
+                        "Reasoning off".to_string()
+                    };
+                    sess.notify(NoticeLevel::Info, text);
+                }
+                KeyCode::Char('o') => {
+                    let expanded = *expand_reasoning.read();
+                    expand_reasoning.set(!expanded);
                 }
+                _ => {}
             }
         }
+        _ => {}
     });
 
-    // Handler for submitting a new message
+    // Handler for continuing the think loop after permission is resolved
+    let continue_thinking = hooks.use_async_handler({
+        move |_: ()| async move {
+            run_think_loop(session, app_state).await;
+        }
+    });
+
+    // Handler for submitting a new message or slash command
     let on_submit = hooks.use_async_handler({
         move |value: String| async move {
-            session.write().message(value).unwrap();
             app_state.set(AppState::Thinking);
 
-            loop {
-                let mut sess = (*session.read()).clone();
-                let result = sess.think_step().await.unwrap();
-                *session.write() = sess;
+            let prompt = if is_command(&value) {
+                run_command(session, app_state, &value).await
+            } else {
+                Some(value)
+            };
+            let Some(prompt) = prompt else {
+                return;
+            };
 
-                match result {
-                    ThinkResult::Done => {
-                        app_state.set(AppState::Idle);
-                        break;
-                    }
-                    ThinkResult::NeedsPermission(requests) => {
-                        app_state.set(AppState::AwaitingPermission(requests));
-                        break;
-                    }
-                    ThinkResult::Continue => {
-                        // Keep looping
-                    }
-                }
+            let mut sess = (*session.read()).clone();
+            let compacted = sess.compact_if_needed().await;
+            *session.write() = sess;
+            match compacted {
+                Ok(true) => session.write().notify(
+                    NoticeLevel::Info,
+                    "Context nearly full: conversation compacted.",
+                ),
+                Ok(false) => {}
+                Err(err) => session
+                    .write()
+                    .notify(NoticeLevel::Warning, format!("Failed to compact: {err}")),
+            }
+
+            // Inline the MCP resources mentioned as `@server:uri`
+            let mcp = session.read().mcp.clone();
+            let (prompt, errors) = expand_resource_mentions(&mcp, &prompt).await;
+            for err in errors {
+                session
+                    .write()
+                    .notify(NoticeLevel::Warning, format!("Failed to read {err}"));
             }
+
+            session.write().message(prompt).unwrap();
+            run_think_loop(session, app_state).await;
         }
     });
 
+    // Handler for picker choice: the chosen value is passed back to the command
+    let submit_pick = on_submit.clone();
+    let mut on_pick = move |command: String, value: Option<String>| match value {
+        Some(value) => submit_pick(format!("/{command} {value}")),
+        None => app_state.set(AppState::Idle),
+    };
+
+    // Handler for the "budget reached" prompt
+    let continue_budget = continue_thinking.clone();
+    let mut on_budget_choice = move |limit: BudgetLimit, value: Option<String>| {
+        if value.as_deref() == Some("continue") {
+            session.write().extend_budget(&limit);
+            app_state.set(AppState::Thinking);
+            continue_budget(());
+        } else {
+            session
+                .write()
+                .notify(NoticeLevel::Warning, format!("Stopped: budget reached ({limit})."));
+            app_state.set(AppState::Idle);
+        }
+    };
+
     // Handler for permission choice
     let mut on_permission_choice = {
         move |choice: PermissionChoice, requests: Vec<PermissionRequest>| {
@@ -137,7 +296,16 @@ pub fn Terminal(mut hooks: Hooks, props: &TerminalProps) -> impl Into<AnyElement
     let current_state = (*app_state.read()).clone();
     let sess = session.read();
 
-    let model_info = get_model_info(&sess.model);
+    let model_info = sess.model_info();
+    let completions: Vec<Completion> = sess
+        .commands
+        .list()
+        .into_iter()
+        .map(|c| Completion {
+            value: format!("/{}", c.name),
+            description: c.description,
+        })
+        .collect();
 
     element! {
       View (flex_direction: FlexDirection::Column) {
@@ -146,7 +314,7 @@ pub fn Terminal(mut hooks: Hooks, props: &TerminalProps) -> impl Into<AnyElement
             MixedText(align: TextAlign::Center, contents: vec![
                 MixedTextContent::new("Enchant CLI").color(COLOR_PRIMARY).weight(Weight::Bold),
                 MixedTextContent::new(" · ").weight(Weight::Bold),
-                MixedTextContent::new(&model_info.name.clone().unwrap_or(sess.model.clone()))
+                MixedTextContent::new(model_info.name.clone().unwrap_or(sess.model.clone()))
                     .color(model_info.get_color()).weight(Weight::Bold),
                 MixedTextContent::new(" · ").weight(Weight::Bold),
                 MixedTextContent::new(sess.working_directory.display())
@@ -154,10 +322,23 @@ pub fn Terminal(mut hooks: Hooks, props: &TerminalProps) -> impl Into<AnyElement
             ])
         }
         View(flex_direction: FlexDirection::Column) {
-            #(session.read().messages.iter().map(|m| {
-                element! {
-                    Message (message: m.clone(), toolset: sess.tools.clone())
-                }
+            #((0..=sess.messages.len()).flat_map(|i| {
+                let notices = sess.notices.iter().filter(move |n| n.position == i).map(|n| {
+                    element! { NoticeMessage(notice: n.clone()) }.into_any()
+                });
+                let message = sess.messages.get(i).map(|m| {
+                    element! {
+                        Message (
+                            message: m.clone(),
+                            toolset: sess.tools.clone(),
+                            tool_activity: sess.tool_activity.clone(),
+                            tool_errors: sess.tool_errors.clone(),
+                            reasoning: sess.reasoning.get(&i).cloned(),
+                            expand_reasoning: *expand_reasoning.read(),
+                        )
+                    }.into_any()
+                });
+                notices.chain(message).collect::<Vec<_>>()
             }))
         }
 
@@ -166,6 +347,9 @@ pub fn Terminal(mut hooks: Hooks, props: &TerminalProps) -> impl Into<AnyElement
                 AppState::Thinking => Some(element! {
                     ThinkingIndicator()
                 }),
+                AppState::Retrying(retry) => Some(element! {
+                    ThinkingIndicator(retry: Some(retry.clone()))
+                }),
                 _ => None,
             })
         }
@@ -190,10 +374,41 @@ pub fn Terminal(mut hooks: Hooks, props: &TerminalProps) -> impl Into<AnyElement
                     )
                 }.into_any()
             }
+            AppState::BudgetReached(ref limit) => {
+                let limit = limit.clone();
+                let option = |value: &str, label: &str, description: &str| PickerOption {
+                    value: value.to_string(),
+                    label: label.to_string(),
+                    description: description.to_string(),
+                    color: None,
+                };
+                element! {
+                    PickerPrompt(
+                        title: format!("Budget reached: {limit}. Continue?"),
+                        options: vec![
+                            option("continue", "Continue", "Allow the agent to run as long again"),
+                            option("stop", "Stop", "End this turn"),
+                        ],
+                        on_choice: move |value| on_budget_choice(limit.clone(), value),
+                    )
+                }.into_any()
+            }
+            AppState::Picking(ref picker) => {
+                let command = picker.command.clone();
+                element! {
+                    PickerPrompt(
+                        title: picker.title.clone(),
+                        options: picker.options.clone(),
+                        selected: picker.selected,
+                        on_choice: move |value| on_pick(command.clone(), value),
+                    )
+                }.into_any()
+            }
             _ => {
                 element! {
                     InputBox(
                         value: input.to_string(),
+                        completions: completions,
                         on_change: move |new_value| input.set(new_value),
                         on_submit: move |value| {
                             on_submit(value);
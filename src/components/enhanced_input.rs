@@ -1,6 +1,8 @@
 use iocraft::prelude::*;
 use std::collections::VecDeque;
 
+use crate::components::COLOR_PRIMARY;
+
 //   | Feature              | Keybinding               |
 //   |----------------------|--------------------------|
 //   | Undo                 | Ctrl+Z                   |
@@ -17,6 +19,8 @@ use std::collections::VecDeque;
 //   | Document end         | Ctrl+End                 |
 //   | Submit (multiline)   | Ctrl+Enter               |
 //   | Insert tab           | Tab (4 spaces)           |
+//   | Select completion    | Up / Down                |
+//   | Accept completion    | Tab                      |
 
 const MAX_UNDO_HISTORY: usize = 100;
 
@@ -433,6 +437,24 @@ fn is_word_boundary_char(c: char) -> bool {
     )
 }
 
+/// An autocomplete suggestion, shown when the input starts with `/`.
+#[derive(Clone, Debug, Default)]
+pub struct Completion {
+    pub value: String,
+    pub description: String,
+}
+
+/// The completions to offer for the current text, if any.
+fn matching_completions<'a>(text: &str, completions: &'a [Completion]) -> Vec<&'a Completion> {
+    if !text.starts_with('/') || text.contains(char::is_whitespace) {
+        return vec![];
+    }
+    completions
+        .iter()
+        .filter(|c| c.value.starts_with(text))
+        .collect()
+}
+
 #[derive(Default, Props)]
 pub struct EnhancedInputProps {
     pub value: String,
@@ -445,6 +467,8 @@ pub struct EnhancedInputProps {
     /// If false (default in multiline), Enter inserts newline and Ctrl+Enter submits.
     pub submit_on_enter: bool,
     pub has_focus: bool,
+    /// Suggestions shown in a popup while the input starts with `/`.
+    pub completions: Vec<Completion>,
 }
 
 #[component]
@@ -452,12 +476,16 @@ pub fn EnhancedInput(
     mut hooks: Hooks,
     props: &mut EnhancedInputProps,
 ) -> impl Into<AnyElement<'static>> {
-    let mut buffer = hooks.use_ref(|| TextBuffer::new());
+    let mut buffer = hooks.use_ref(TextBuffer::new);
     let text_input_handle = hooks.use_ref(TextInputHandle::default);
     let mut on_change = props.on_change.take();
     let mut on_submit = props.on_submit.take();
     let multiline = props.multiline;
     let submit_on_enter = props.submit_on_enter;
+    let mut selected = hooks.use_state(|| 0usize);
+    // Whether Up/Down moved the selection since the text last changed.
+    let mut moved = hooks.use_state(|| false);
+    let completions = props.completions.clone();
 
     // Sync external value changes to buffer
     {
@@ -468,8 +496,8 @@ pub fn EnhancedInput(
     }
 
     hooks.use_terminal_events({
-        let mut buffer = buffer.clone();
-        let mut text_input_handle = text_input_handle.clone();
+        let mut buffer = buffer;
+        let mut text_input_handle = text_input_handle;
         move |event| {
             if let TerminalEvent::Key(KeyEvent {
                 kind,
@@ -489,6 +517,46 @@ pub fn EnhancedInput(
                 let mut buf = buffer.write();
                 let mut changed = false;
 
+                // While completions are shown, Up/Down select and Tab accepts
+                // the selected one. Enter submits the text as typed, unless
+                // the selection was moved.
+                let matches = matching_completions(buf.text(), &completions);
+                if !matches.is_empty() {
+                    let index = (*selected.read()).min(matches.len() - 1);
+                    match code {
+                        KeyCode::Up => {
+                            selected.set((index + matches.len() - 1) % matches.len());
+                            moved.set(true);
+                            return;
+                        }
+                        KeyCode::Down => {
+                            selected.set((index + 1) % matches.len());
+                            moved.set(true);
+                            return;
+                        }
+                        KeyCode::Tab => {
+                            buf.set_text(format!("{} ", matches[index].value));
+                            buf.move_cursor_to_end();
+                            text_input_handle.write().set_cursor_offset(buf.cursor());
+                            let text = buf.text().to_string();
+                            drop(buf);
+                            selected.set(0);
+                            moved.set(false);
+                            (on_change)(text);
+                            return;
+                        }
+                        KeyCode::Enter if !shift && *moved.read() => {
+                            let value = matches[index].value.clone();
+                            drop(buf);
+                            selected.set(0);
+                            moved.set(false);
+                            (on_submit)(value);
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+
                 // Enter key handling depends on mode:
                 // - submit_on_enter: Enter submits, Shift+Enter inserts newline
                 // - !submit_on_enter (multiline default): Enter inserts newline, Ctrl+Enter submits
@@ -615,6 +683,8 @@ pub fn EnhancedInput(
                 if changed {
                     let text = buf.text().to_string();
                     drop(buf);
+                    selected.set(0);
+                    moved.set(false);
                     (on_change)(text);
                 }
             }
@@ -622,18 +692,37 @@ pub fn EnhancedInput(
     });
 
     let value = buffer.read().text().to_string();
+    let matches = matching_completions(&value, &props.completions);
+    let index = (*selected.read()).min(matches.len().saturating_sub(1));
+    let width = matches.iter().map(|c| c.value.len()).max().unwrap_or(0);
 
     element! {
-        TextInput(
-            has_focus: props.has_focus,
-            value: value,
-            color: props.color,
-            cursor_color: props.cursor_color,
-            multiline: props.multiline,
-            handle: Some(text_input_handle),
-            on_change: move |_new_value| {
-                // Ignore external changes from TextInput - we manage state ourselves
-            },
-        )
+        View(flex_direction: FlexDirection::Column) {
+            TextInput(
+                has_focus: props.has_focus,
+                value: value,
+                color: props.color,
+                cursor_color: props.cursor_color,
+                multiline: props.multiline,
+                handle: Some(text_input_handle),
+                on_change: move |_new_value| {
+                    // Ignore external changes from TextInput - we manage state ourselves
+                },
+            )
+            #(if !matches.is_empty() {
+                Some(element! {
+                    View(flex_direction: FlexDirection::Column, margin_top: 1) {
+                        #(matches.iter().enumerate().map(|(i, c)| {
+                            let color = if i == index { COLOR_PRIMARY } else { Color::DarkGrey };
+                            element! {
+                                Text(content: format!("{:width$}  {}", c.value, c.description), color: color)
+                            }
+                        }))
+                    }
+                })
+            } else {
+                None
+            })
+        }
     }
 }
@@ -1,13 +1,15 @@
 use iocraft::prelude::*;
 
 use crate::components::COLOR_PRIMARY;
-use crate::components::enhanced_input::EnhancedInput;
+use crate::components::enhanced_input::{Completion, EnhancedInput};
 
 #[derive(Default, Props)]
 pub struct InputBoxProps {
     pub value: String,
     pub on_change: HandlerMut<'static, String>,
     pub on_submit: HandlerMut<'static, String>,
+    /// Slash commands offered when the input starts with `/`.
+    pub completions: Vec<Completion>,
 }
 
 #[component]
@@ -31,6 +33,7 @@ pub fn InputBox(mut hooks: Hooks, props: &mut InputBoxProps) -> impl Into<AnyEle
                 on_submit: props.on_submit.take(),
                 multiline: true,
                 submit_on_enter: true,
+                completions: props.completions.clone(),
             )
         }
     }
@@ -209,7 +209,7 @@ fn render_node(node: &markdown::mdast::Node, out: &mut Vec<MixedTextContent>, st
         _ => {
             // As a conservative fallback, we try to use `to_string` debug-ish.
             // Better than dropping content.
-            push_text(out, &format!("{}", node_to_plain(node)));
+            push_text(out, &node_to_plain(node));
         }
     }
 }
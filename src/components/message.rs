@@ -1,4 +1,6 @@
-use genai::chat::{ChatMessage, ChatRole};
+use std::collections::HashMap;
+
+use genai::chat::{ChatMessage, ChatRole, ContentPart};
 use iocraft::prelude::*;
 
 use crate::components::COLOR_PRIMARY;
@@ -9,18 +11,29 @@ const COLOR_TOOL: Color = Color::Rgb {
     b: 237,
 }; // Cornflower blue
 
+/// How many lines of nested tool activity are shown under a collapsed call.
+const ACTIVITY_PREVIEW_LINES: usize = 3;
+
 #[derive(Default, Props)]
 pub struct MessageProps {
     pub message: Option<ChatMessage>,
     pub toolset: Option<std::sync::Arc<crate::agent::tools::tool::Toolset>>,
+    /// Nested activity of tool calls (call_id -> activity lines), e.g. from sub-agents.
+    pub tool_activity: HashMap<String, Vec<String>>,
+    /// Errors of failed tool calls (call_id -> error), shown under the call.
+    pub tool_errors: HashMap<String, String>,
+    /// Reasoning that preceded the message, shown dimmed above it.
+    pub reasoning: Option<String>,
+    /// Shows the full reasoning instead of a one-line summary.
+    pub expand_reasoning: bool,
 }
 
 fn has_displayable_content(message: &ChatMessage) -> bool {
     message.content.contains_text() || !message.content.tool_calls().is_empty()
 }
 
-fn should_ignore_message(message: &ChatMessage) -> bool {
-    if !has_displayable_content(message) {
+fn should_ignore_message(message: &ChatMessage, reasoning: Option<&String>) -> bool {
+    if !has_displayable_content(message) && reasoning.is_none() {
         return true;
     }
     matches!(message.role, ChatRole::System)
@@ -44,21 +57,63 @@ pub fn Message(mut hooks: Hooks, props: &MessageProps) -> impl Into<AnyElement<'
 
     element! {
         View() {
-            #(if let Some(message) = &props.message && !should_ignore_message(&message) {
+            #(if let Some(message) = &props.message && !should_ignore_message(message, props.reasoning.as_ref()) {
                 let tool_calls = message.content.tool_calls();
                 let text_content = message.content.clone().into_joined_texts().unwrap_or("".to_string());
+                // Images returned by tools are sent in a user message; only their count is shown.
+                let images = message.content.parts().iter().filter(|p| matches!(p, ContentPart::Binary(_))).count();
                 Some(element! {
                     View(flex_direction: FlexDirection::Column, max_width: w) {
+                        #(props.reasoning.as_ref().map(|reasoning| {
+                            let lines = reasoning.trim().lines().count();
+                            element! {
+                                View(max_width: w, border_style: MESSAGE_LINE, padding_left: 1, border_color: Color::DarkGrey, flex_direction: FlexDirection::Column) {
+                                    #(if props.expand_reasoning {
+                                        element! {
+                                            Text(content: format!("∴ Thinking\n{}", reasoning.trim()), color: Color::DarkGrey, italic: true, wrap: TextWrap::Wrap)
+                                        }
+                                    } else {
+                                        element! {
+                                            Text(content: format!("∴ Thought for {lines} lines (ctrl+o to expand)"), color: Color::DarkGrey, italic: true)
+                                        }
+                                    })
+                                }
+                            }
+                        }))
                         #(tool_calls.iter().map(|tc| {
-                            let display = toolset.as_ref().and_then(|ts| Some(ts.describe_action(&tc.fn_name, &tc.fn_arguments)))
+                            let display = toolset.as_ref().map(|ts| ts.describe_action(&tc.fn_name, &tc.fn_arguments))
                                 .unwrap_or_else(|| format!("{}({})", tc.fn_name, tc.fn_arguments));
+                            let activity = props.tool_activity.get(&tc.call_id).cloned().unwrap_or_default();
+                            let hidden = activity.len().saturating_sub(ACTIVITY_PREVIEW_LINES);
+                            let error = props.tool_errors.get(&tc.call_id).map(|e| e.lines().next().unwrap_or_default().to_string());
                             element! {
-                                View(max_width: w, border_style: MESSAGE_LINE, padding_left: 1, border_color: COLOR_TOOL) {
+                                View(max_width: w, border_style: MESSAGE_LINE, padding_left: 1, border_color: if error.is_some() { Color::Red } else { COLOR_TOOL }, flex_direction: FlexDirection::Column) {
                                     Text(content: display, color: COLOR_TOOL, wrap: TextWrap::Wrap)
+                                    #(error.map(|error| element! {
+                                        View(padding_left: 2) {
+                                            Text(content: format!("⎿ Failed: {error}"), color: Color::Red, wrap: TextWrap::Wrap)
+                                        }
+                                    }))
+                                    #(if !activity.is_empty() {
+                                        Some(element! {
+                                            View(flex_direction: FlexDirection::Column, padding_left: 2) {
+                                                Text(content: format!("⎿ {} tool uses{}", activity.len(), if hidden > 0 { format!(" (+{hidden} more)") } else { String::new() }), color: Color::DarkGrey)
+                                                #(activity.iter().skip(hidden).map(|line| element! {
+                                                    Text(content: format!("  {line}"), color: Color::DarkGrey, wrap: TextWrap::Wrap)
+                                                }))
+                                            }
+                                        })
+                                    } else { None })
                                 }
                             }
                         }).collect::<Vec<_>>())
-                        #(if !text_content.is_empty() {
+                        #(if images > 0 {
+                            Some(element! {
+                                View(max_width: w, border_style: MESSAGE_LINE, padding_left: 1, border_color: COLOR_TOOL) {
+                                    Text(content: format!("{text_content} {images} attached"), color: Color::DarkGrey)
+                                }
+                            })
+                        } else if !text_content.is_empty() {
                             Some(element! {
                                 View (max_width: w, border_style: MESSAGE_LINE, padding_left: 1, border_color: match message.role {
                                     ChatRole::Assistant => Some(COLOR_PRIMARY),
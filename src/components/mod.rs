@@ -4,7 +4,9 @@ pub mod enhanced_input;
 pub mod input_box;
 pub mod message;
 pub mod markdown_mixed_text;
+pub mod notice_message;
 pub mod permission_prompt;
+pub mod picker_prompt;
 pub mod status_line;
 pub mod thinking_indicator;
 
@@ -14,6 +16,7 @@ pub use input_box::*;
 pub use markdown_mixed_text::*;
 use iocraft::Color;
 pub use permission_prompt::*;
+pub use picker_prompt::*;
 pub use status_line::*;
 pub use thinking_indicator::*;
 
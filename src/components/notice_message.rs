@@ -0,0 +1,29 @@
+use iocraft::prelude::*;
+
+use crate::agent::notice::{Notice, NoticeLevel};
+
+#[derive(Default, Props)]
+pub struct NoticeMessageProps {
+    pub notice: Option<Notice>,
+}
+
+/// A client-side notice in the transcript, e.g. the output of a slash command.
+#[component]
+pub fn NoticeMessage(mut hooks: Hooks, props: &NoticeMessageProps) -> impl Into<AnyElement<'static>> {
+    let (w, _) = hooks.use_terminal_size();
+
+    element! {
+        View(max_width: w, padding_left: 2) {
+            #(props.notice.as_ref().map(|notice| {
+                let color = match notice.level {
+                    NoticeLevel::Info => Color::DarkGrey,
+                    NoticeLevel::Warning => Color::Yellow,
+                    NoticeLevel::Error => Color::Red,
+                };
+                element! {
+                    Text(content: notice.text.clone(), color: color, wrap: TextWrap::Wrap)
+                }
+            }))
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use iocraft::prelude::*;
+
+use crate::{commands::PickerOption, components::COLOR_PRIMARY};
+
+#[derive(Default, Props)]
+pub struct PickerPromptProps {
+    pub title: String,
+    pub options: Vec<PickerOption>,
+    /// Index of the option selected initially.
+    pub selected: usize,
+    /// Called with the chosen value, or `None` if the picker was dismissed.
+    pub on_choice: HandlerMut<'static, Option<String>>,
+}
+
+#[component]
+pub fn PickerPrompt(
+    mut hooks: Hooks,
+    props: &mut PickerPromptProps,
+) -> impl Into<AnyElement<'static>> {
+    let initial = props.selected;
+    let mut selected = hooks.use_state(move || initial);
+    let mut on_choice = props.on_choice.take();
+    let values: Vec<String> = props.options.iter().map(|o| o.value.clone()).collect();
+
+    let (w, _) = hooks.use_terminal_size();
+
+    hooks.use_terminal_events({
+        move |event| match event {
+            TerminalEvent::Key(KeyEvent { kind, code, .. })
+                if kind != KeyEventKind::Release && !values.is_empty() =>
+            {
+                let index = *selected.read();
+                match code {
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        selected.set((index + values.len() - 1) % values.len());
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        selected.set((index + 1) % values.len());
+                    }
+                    KeyCode::Enter => {
+                        (on_choice)(values.get(index).cloned());
+                    }
+                    KeyCode::Esc => {
+                        (on_choice)(None);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    });
+
+    let index = *selected.read();
+    let width = props
+        .options
+        .iter()
+        .map(|o| o.label.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    element! {
+        View(
+            flex_direction: FlexDirection::Column,
+            border_style: BorderStyle::Single,
+            border_color: COLOR_PRIMARY,
+            padding: 1,
+            max_width: w,
+        ) {
+            Text(content: props.title.clone(), weight: Weight::Bold, color: COLOR_PRIMARY)
+            View(margin_top: 1, flex_direction: FlexDirection::Column) {
+                #(props.options.iter().enumerate().map(|(i, option)| {
+                    let marker = if i == index { "❯ " } else { "  " };
+                    let color = if i == index {
+                        option.color.unwrap_or(COLOR_PRIMARY)
+                    } else {
+                        Color::Grey
+                    };
+                    element! {
+                        MixedText(contents: vec![
+                            MixedTextContent::new(format!("{marker}{:width$}", option.label))
+                                .color(color)
+                                .weight(if i == index { Weight::Bold } else { Weight::Normal }),
+                            MixedTextContent::new(format!("  {}", option.description)).color(Color::DarkGrey),
+                        ])
+                    }
+                }))
+            }
+            View(margin_top: 1) {
+                Text(
+                    content: "Use arrow keys to choose, Enter to confirm, Esc to cancel",
+                    color: Color::DarkGrey,
+                )
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use iocraft::prelude::*;
 
 use crate::{
-    agent::{Session, models::model_info::get_model_info},
+    agent::{Session, usage::{format_cost, format_tokens}},
     components::COLOR_PRIMARY,
 };
 
@@ -12,20 +12,42 @@ pub struct StatusLineProps {
 
 #[component]
 pub fn StatusLine(props: &StatusLineProps) -> impl Into<AnyElement<'static>> {
-    let token_text = if let Some(session) = &props.session {
-        let model_info = get_model_info(&session.model);
+    let mut parts = vec![];
+    if let Some(session) = &props.session {
+        let model_info = session.model_info();
+        if let Some(fallback) = &session.fallback {
+            let info = session.models().get(&fallback.model);
+            parts.push(format!(
+                "Fallback: {}",
+                info.name.unwrap_or(fallback.model.clone())
+            ));
+        }
         if let Some(total) = session.total_tokens {
             let mut s = format!("Tokens: {:.1}k", total as f64 / 1e3);
             if let Some(max) = model_info.max_context {
                 s.push_str(&format!(" / {:.1}k", max as f64 / 1e3));
             }
-            s
-        } else {
-            "".to_string()
+            parts.push(s);
+        }
+        // Cache hits and misses of the input of the last call
+        if let Some(last) = session.usage.records.last()
+            && last.tokens.cache_read + last.tokens.cache_write > 0
+        {
+            parts.push(format!(
+                "Cache: {} hit · {} miss",
+                format_tokens(last.tokens.cache_read),
+                format_tokens(last.tokens.input + last.tokens.cache_write)
+            ));
+        }
+        if let Some(reasoning) = session.current_reasoning().filter(|r| r.is_on())
+            && model_info.reasoning != Some(false)
+        {
+            parts.push(format!("Thinking: {reasoning}"));
         }
-    } else {
-        "".to_string()
-    };
+        if !session.usage.records.is_empty() {
+            parts.push(format!("Cost: {}", format_cost(session.usage.total_cost())));
+        }
+    }
 
     element! {
         View (
@@ -34,7 +56,7 @@ pub fn StatusLine(props: &StatusLineProps) -> impl Into<AnyElement<'static>> {
             border_color: COLOR_PRIMARY
         ) {
             Text(
-                content: token_text,
+                content: parts.join(" · "),
                 color: COLOR_PRIMARY
             )
         }
@@ -1,15 +1,32 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use iocraft::prelude::*;
 use rand::seq::IteratorRandom;
 
-use crate::components::COLOR_PRIMARY;
+use crate::{agent::retry::MAX_RETRIES, components::COLOR_PRIMARY};
 
-const ENCHANTING_TEXT: &'static str =
+const ENCHANTING_TEXT: &str =
     "⍑ᒷᓵ∷ᔑ∴ꖎᒷ↸∴╎ℸ⍑ᓵᔑ∷ᒷᔑꖎ𝙹リ⊣ℸ⍑ᒷꖎᒷ↸⊣ᒷ.ℸ⍑ᒷ⎓╎リ∴ᔑᓭᓭ⍑ᔑ∷!¡ᔑリ↸ᓵ⚍ℸ⍑ᓵꖎᒷᔑ∷∴ᔑℸᒷ∷.";
 
+/// A failed model call waiting to be retried.
+#[derive(Clone, Debug)]
+pub struct RetryStatus {
+    pub reason: String,
+    /// The retry being waited for, starting at 1.
+    pub attempt: u32,
+    pub until: Instant,
+}
+
+#[derive(Default, Props)]
+pub struct ThinkingIndicatorProps {
+    pub retry: Option<RetryStatus>,
+}
+
 #[component]
-pub fn ThinkingIndicator(mut hooks: Hooks) -> impl Into<AnyElement<'static>> {
+pub fn ThinkingIndicator(
+    mut hooks: Hooks,
+    props: &ThinkingIndicatorProps,
+) -> impl Into<AnyElement<'static>> {
     let mut indicator = hooks.use_state(|| {
         ENCHANTING_TEXT
             .chars()
@@ -29,7 +46,26 @@ pub fn ThinkingIndicator(mut hooks: Hooks) -> impl Into<AnyElement<'static>> {
         }
     });
 
+    // The indicator changes every 200ms, which also refreshes the countdown.
+    let (content, color) = match &props.retry {
+        Some(retry) => {
+            let seconds = retry
+                .until
+                .saturating_duration_since(Instant::now())
+                .as_secs_f64()
+                .ceil();
+            (
+                format!(
+                    "{} {} · retrying in {seconds}s ({}/{MAX_RETRIES})",
+                    indicator, retry.reason, retry.attempt
+                ),
+                Color::Yellow,
+            )
+        }
+        None => (format!("{} Enchanting...", indicator), COLOR_PRIMARY),
+    };
+
     element! {
-        Text (content: format!("{} Enchanting...", indicator), color: COLOR_PRIMARY)
+        Text (content, color, wrap: TextWrap::Wrap)
     }
 }
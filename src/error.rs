@@ -1,21 +1,159 @@
-use std::io;
+use std::{fmt, io, path::PathBuf, time::Duration};
 
 use crate::agent::tools::tool_error::ToolError;
 
 #[derive(Debug)]
 pub enum Error {
     AIError(genai::Error),
+    /// The API key was missing or rejected.
+    AuthError(String),
+    /// Too many requests; `retry_after` is the delay asked for by the provider.
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The provider is overloaded or failed on its side.
+    Overloaded(String),
+    /// The conversation does not fit in the model's context window.
+    ContextTooLong(String),
+    /// The provider could not be reached, or the connection was dropped.
+    NetworkError(String),
     ToolError(ToolError),
     IOError(io::Error),
     SerdeError(String),
+    /// A config file that doesn't match its schema, with a problem per line.
+    ConfigError {
+        path: PathBuf,
+        problems: Vec<String>,
+    },
+    CommandError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Resolver errors carry our own messages, e.g. a missing API key.
+            Self::AIError(genai::Error::Resolver {
+                resolver_error: genai::resolver::Error::Custom(message),
+                ..
+            }) => write!(f, "{message}"),
+            Self::AIError(err) => write!(f, "{err}"),
+            Self::AuthError(message) => {
+                write!(f, "Authentication failed, check your API key: {message}")
+            }
+            Self::RateLimited { message, .. } => write!(f, "Rate limited: {message}"),
+            Self::Overloaded(message) => write!(f, "Provider unavailable: {message}"),
+            Self::ContextTooLong(message) => write!(
+                f,
+                "The conversation is too long for the model, use /compact to shorten it: {message}"
+            ),
+            Self::NetworkError(message) => write!(f, "Network error: {message}"),
+            Self::ToolError(err) => write!(f, "{err}"),
+            Self::IOError(err) => write!(f, "{err}"),
+            Self::SerdeError(message) | Self::CommandError(message) => write!(f, "{message}"),
+            Self::ConfigError { path, problems } => match problems.as_slice() {
+                [problem] => write!(f, "Invalid {}: {problem}", path.display()),
+                _ => write!(f, "Invalid {}:\n  {}", path.display(), problems.join("\n  ")),
+            },
+        }
+    }
+}
+
+impl Error {
+    /// Whether the call that failed may succeed if it is made again.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. } | Self::Overloaded(_) | Self::NetworkError(_)
+        )
+    }
+
+    /// Whether another model, possibly from another provider, may succeed
+    /// where this one failed.
+    pub fn should_fall_back(&self) -> bool {
+        self.is_transient() || matches!(self, Self::AuthError(_))
+    }
+
+    /// The delay the provider asked to wait before retrying, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
 }
 
 impl From<genai::Error> for Error {
     fn from(err: genai::Error) -> Self {
-        Self::AIError(err)
+        use genai::webc::Error as WebError;
+
+        let webc_error = match &err {
+            genai::Error::WebModelCall { webc_error, .. }
+            | genai::Error::WebAdapterCall { webc_error, .. } => webc_error,
+            genai::Error::RequiresApiKey { .. } | genai::Error::NoAuthData { .. } => {
+                return Self::AuthError(err.to_string());
+            }
+            genai::Error::ReqwestEventSource(_) | genai::Error::WebStream { .. } => {
+                return Self::NetworkError(err.to_string());
+            }
+            _ => return Self::AIError(err),
+        };
+
+        match webc_error {
+            WebError::ResponseFailedStatus {
+                status,
+                body,
+                headers,
+            } => {
+                let message = error_message(body);
+                match status.as_u16() {
+                    401 | 403 => Self::AuthError(message),
+                    429 => Self::RateLimited {
+                        message,
+                        retry_after: headers
+                            .get("retry-after")
+                            .and_then(|v| v.to_str().ok())
+                            .and_then(|v| v.trim().parse().ok())
+                            .map(Duration::from_secs),
+                    },
+                    400 | 413 if is_context_error(&message) => Self::ContextTooLong(message),
+                    500..=599 => Self::Overloaded(message),
+                    _ => Self::AIError(err),
+                }
+            }
+            WebError::Reqwest(_) => Self::NetworkError(webc_error.to_string()),
+            _ => Self::AIError(err),
+        }
     }
 }
 
+/// The message of a provider error body such as `{"error": {"message": "..."}}`.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| {
+            let error = value.get("error").unwrap_or(&value);
+            error
+                .get("message")
+                .or(error.as_str().map(|_| error))
+                .and_then(|m| m.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+fn is_context_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    [
+        "prompt is too long",
+        "context length",
+        "context window",
+        "too many tokens",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
 impl From<ToolError> for Error {
     fn from(err: ToolError) -> Self {
         Self::ToolError(err)
@@ -33,3 +171,9 @@ impl From<serde_json::Error> for Error {
         Self::SerdeError(err.to_string())
     }
 }
+
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self::SerdeError(err.to_string())
+    }
+}
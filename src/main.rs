@@ -1,7 +1,18 @@
 use clap::Parser;
 use iocraft::prelude::*;
 
-use crate::components::app::App;
+use crate::{
+    agent::{
+        budget::BudgetConfig,
+        config::{
+            Config, config_schema, effective_settings, load_config, parse_overrides,
+            validate_config_files,
+        },
+        mcp::serve::serve_stdio,
+    },
+    components::app::App,
+    util::env::{References, redact},
+};
 
 pub mod agent;
 pub mod commands;
@@ -9,13 +20,177 @@ pub mod components;
 pub mod error;
 pub mod util;
 #[derive(clap::Parser)]
-struct Cli {}
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Model to start the session with, overriding `default_model`.
+    #[arg(long, global = true)]
+    model: Option<String>,
+
+    /// Maximum model calls per prompt.
+    #[arg(long, global = true)]
+    max_turns: Option<usize>,
+
+    /// Maximum tool calls per session.
+    #[arg(long, global = true)]
+    max_tool_calls: Option<usize>,
+
+    /// Maximum tokens per session.
+    #[arg(long, global = true)]
+    max_tokens: Option<u64>,
+
+    /// Maximum cost per session, in dollars.
+    #[arg(long, global = true)]
+    max_cost: Option<f64>,
+
+    /// A setting applied over the config files, e.g. `budget.max_cost=5`.
+    /// Values are JSON, or else strings. Can be repeated.
+    #[arg(long = "config", value_name = "KEY=VALUE", global = true)]
+    config: Vec<String>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Check or show the configuration.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Work with the Model Context Protocol.
+    Mcp {
+        #[command(subcommand)]
+        command: McpCommand,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum ConfigCommand {
+    /// Check the user's config files and the project's `enchant.json` and
+    /// `enchant.local.json` against the schema.
+    Validate,
+    /// Print the effective value of each setting.
+    Show {
+        /// Also print the file each setting comes from.
+        #[arg(long)]
+        origin: bool,
+    },
+    /// Print the JSON Schema of `enchant.json`.
+    Schema,
+}
+
+#[derive(clap::Subcommand)]
+enum McpCommand {
+    /// Serve the built-in tools over stdio, for other MCP clients. Calls that
+    /// need approval are rejected.
+    Serve,
+}
 
 #[tokio::main]
 async fn main() {
-    dotenvy::dotenv().unwrap();
+    // A `.env` file is optional, e.g. when started by another MCP client.
+    dotenvy::dotenv().ok();
+
+    let args = Cli::parse();
+    // `config` subcommands show and check the config without running commands.
+    let references = match args.command {
+        Some(Command::Config { .. }) => References::Keep,
+        _ => References::Resolve,
+    };
+    // `--config` comes first, so that the dedicated flags win.
+    let overrides = match parse_overrides(&args.config, references) {
+        Ok(config) => config.merge(Config {
+            default_model: args.model,
+            budget: BudgetConfig {
+                max_turns: args.max_turns,
+                max_tool_calls: args.max_tool_calls,
+                max_tokens: args.max_tokens,
+                max_cost: args.max_cost,
+            },
+            ..Default::default()
+        }),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    match args.command {
+        Some(Command::Mcp {
+            command: McpCommand::Serve,
+        }) => {
+            if let Err(err) = serve_stdio(overrides).await {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Config {
+            command: ConfigCommand::Validate,
+        }) => {
+            let working_directory = std::env::current_dir().unwrap();
+            let results = validate_config_files(&working_directory).await;
+            if results.is_empty() {
+                println!("No config files found.");
+            }
+            let mut valid = true;
+            for (path, result) in results {
+                match result {
+                    Ok(()) => println!("{} is valid", path.display()),
+                    Err(err) => {
+                        valid = false;
+                        println!("{err}");
+                    }
+                }
+            }
+            std::process::exit(if valid { 0 } else { 1 });
+        }
+        Some(Command::Config {
+            command: ConfigCommand::Show { origin },
+        }) => {
+            show_config(overrides, origin).await;
+            return;
+        }
+        Some(Command::Config {
+            command: ConfigCommand::Schema,
+        }) => {
+            println!("{}", serde_json::to_string_pretty(&config_schema()).unwrap());
+            return;
+        }
+        None => {}
+    }
 
-    let _args = Cli::parse();
     print!("{}[2J", 27 as char); // clear console
-    element!(App).render_loop().await.unwrap();
+    element!(App(overrides: overrides)).render_loop().await.unwrap();
+}
+
+/// `enchant config show`: one `key = value` line per setting, with references as written.
+async fn show_config(overrides: Config, origin: bool) {
+    let mut config_state = match load_config(References::Keep).await {
+        Ok(config_state) => config_state,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    config_state.overrides = overrides;
+    let working_directory = std::env::current_dir().unwrap();
+    let (layers, errors) = config_state.layers(&working_directory).await;
+    for warning in config_state
+        .warnings
+        .iter()
+        .cloned()
+        .chain(errors.iter().map(|err| format!("{err}\nThe file is ignored.")))
+    {
+        eprintln!("Warning: {warning}");
+    }
+
+    for setting in effective_settings(&layers) {
+        let line = redact(&format!("{} = {}", setting.key, setting.value));
+        if origin {
+            println!("{line}  ({})", setting.origins.join(", "));
+        } else {
+            println!("{line}");
+        }
+    }
 }
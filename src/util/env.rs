@@ -0,0 +1,265 @@
+//! `${...}` references in the string values of config files:
+//!
+//! - `${VAR}` is the environment variable `VAR`, and `${VAR:-default}` falls
+//!   back to `default` when it is unset or empty.
+//! - `${key:NAME}` is the API key of the entry `NAME` in `api-keys.json`.
+//! - `${cmd:COMMAND}` is the output of a shell command, e.g. a password manager CLI.
+//! - `$${` is a literal `${`.
+//!
+//! `key:` and `cmd:` are only allowed in trusted values, i.e. the user's own
+//! config files: a project's files come with the repository, and checking one
+//! out shouldn't run commands or hand out API keys.
+//!
+//! Values of `key:` and `cmd:` references are treated as secrets: `redact` hides
+//! them wherever config is shown. Environment variables are not, as most are
+//! not secret and short values like `1` would be hidden everywhere.
+
+use std::{env, process::Command, sync::Mutex};
+
+use serde_json::Value;
+
+use crate::error::Error;
+
+/// Values resolved from `key:` and `cmd:` references, hidden by `redact`.
+static SECRETS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// What is done with references.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum References {
+    /// They are replaced with their values.
+    Resolve,
+    /// They are checked, but no command is run and values are left as
+    /// written, e.g. to show or validate the config.
+    Keep,
+}
+
+/// How the references of a value are handled.
+#[derive(Clone, Copy)]
+pub struct Interpolation<'a> {
+    /// Looks up API keys by name, for `${key:NAME}`.
+    pub keys: &'a (dyn Fn(&str) -> Option<String> + Sync),
+    /// Whether `${key:...}` and `${cmd:...}` are allowed.
+    pub trusted: bool,
+    pub references: References,
+}
+
+/// Replaces all references in `value`.
+pub fn interpolate(value: &str, interpolation: &Interpolation) -> Result<String, Error> {
+    interpolate_with(value, &|name| env::var(name).ok(), interpolation)
+}
+
+/// Replaces the references in every string of a JSON config. Errors name
+/// the path of the value, e.g. `mcp_servers[0].env.TOKEN`.
+pub fn interpolate_json(value: &mut Value, interpolation: &Interpolation) -> Result<(), Error> {
+    interpolate_json_at(value, "", interpolation)
+}
+
+fn interpolate_json_at(
+    value: &mut Value,
+    path: &str,
+    interpolation: &Interpolation,
+) -> Result<(), Error> {
+    match value {
+        Value::String(text) if text.contains("${") => {
+            *text = interpolate(text, interpolation)
+                .map_err(|e| Error::SerdeError(format!("{e} (at {path})")))?;
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                interpolate_json_at(item, &format!("{path}[{i}]"), interpolation)?;
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                interpolate_json_at(item, &path, interpolation)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replaces the values resolved from references in `text` with `***`.
+pub fn redact(text: &str) -> String {
+    let secrets = SECRETS.lock().unwrap();
+    secrets
+        .iter()
+        .fold(text.to_string(), |text, secret| text.replace(secret, "***"))
+}
+
+fn remember_secret(value: &str) {
+    let mut secrets = SECRETS.lock().unwrap();
+    if !value.is_empty() && !secrets.iter().any(|s| s == value) {
+        secrets.push(value.to_string());
+    }
+}
+
+fn interpolate_with(
+    value: &str,
+    env: &dyn Fn(&str) -> Option<String>,
+    interpolation: &Interpolation,
+) -> Result<String, Error> {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        out.push_str(&rest[..start]);
+        // Commands may contain braces, so find the matching one.
+        let mut depth = 0;
+        let end = rest[start + 2..].char_indices().find_map(|(i, c)| match c {
+            '{' => {
+                depth += 1;
+                None
+            }
+            '}' if depth == 0 => Some(start + 2 + i),
+            '}' => {
+                depth -= 1;
+                None
+            }
+            _ => None,
+        });
+        let Some(end) = end else {
+            return Err(Error::SerdeError(format!(
+                "Unterminated `${{` in {value:?}"
+            )));
+        };
+        out.push_str(&resolve(&rest[start + 2..end], env, interpolation)?);
+        rest = &rest[end + 1..];
+    }
+    if interpolation.references == References::Keep {
+        return Ok(value.to_string());
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn resolve(
+    reference: &str,
+    env: &dyn Fn(&str) -> Option<String>,
+    interpolation: &Interpolation,
+) -> Result<String, Error> {
+    let untrusted = || {
+        Error::SerdeError(format!(
+            "`${{{reference}}}` can only be used in the user's config files"
+        ))
+    };
+    if let Some(name) = reference.strip_prefix("key:")
+        && !name.starts_with('-')
+    {
+        if !interpolation.trusted {
+            return Err(untrusted());
+        }
+        let key = (interpolation.keys)(name)
+            .ok_or_else(|| Error::SerdeError(format!("No API key named {name} in api-keys.json")))?;
+        remember_secret(&key);
+        return Ok(key);
+    }
+    if let Some(command) = reference.strip_prefix("cmd:")
+        && !command.starts_with('-')
+    {
+        if !interpolation.trusted {
+            return Err(untrusted());
+        }
+        if interpolation.references == References::Keep {
+            return Ok(String::new());
+        }
+        let output = run_command(command)?;
+        remember_secret(&output);
+        return Ok(output);
+    }
+
+    let (name, default) = match reference.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (reference, None),
+    };
+    env(name)
+        .filter(|value| !value.is_empty())
+        .or_else(|| default.map(str::to_string))
+        .ok_or_else(|| Error::SerdeError(format!("Environment variable {name} is not set")))
+}
+
+/// The trimmed output of `sh -c command`.
+fn run_command(command: &str) -> Result<String, Error> {
+    let output = Command::new("sh").arg("-c").arg(command).output()?;
+    if !output.status.success() {
+        return Err(Error::SerdeError(format!(
+            "`{command}` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        let env = |name: &str| (name == "TOKEN").then(|| "secret".to_string());
+        let keys = |name: &str| (name == "github").then(|| "ghp_key".to_string());
+        let trusted = Interpolation {
+            keys: &keys,
+            trusted: true,
+            references: References::Resolve,
+        };
+        let interpolate = |value| interpolate_with(value, &env, &trusted);
+        assert_eq!(interpolate("Bearer ${TOKEN}").unwrap(), "Bearer secret");
+        assert_eq!(interpolate("no vars").unwrap(), "no vars");
+        assert_eq!(interpolate("${MISSING:-none}").unwrap(), "none");
+        assert_eq!(interpolate("${TOKEN:-none}").unwrap(), "secret");
+        assert_eq!(interpolate("${key:github}").unwrap(), "ghp_key");
+        assert_eq!(interpolate("$${TOKEN} is ${TOKEN}").unwrap(), "${TOKEN} is secret");
+        assert_eq!(
+            interpolate("${cmd:echo '{\"a\": 1}'}").unwrap(),
+            "{\"a\": 1}"
+        );
+        assert!(interpolate("${MISSING}").is_err());
+        assert!(interpolate("${key:gitlab}").is_err());
+        assert!(interpolate("${cmd:exit 1}").is_err());
+        assert!(interpolate("${TOKEN").is_err());
+
+        assert_eq!(redact("token=secret, key=ghp_key"), "token=secret, key=***");
+
+        let project = Interpolation {
+            trusted: false,
+            ..trusted
+        };
+        assert_eq!(interpolate_with("${TOKEN}", &env, &project).unwrap(), "secret");
+        let err = interpolate_with("${cmd:touch /tmp/enchant-test}", &env, &project).unwrap_err();
+        assert!(err.to_string().contains("can only be used in the user's config files"));
+        assert!(interpolate_with("${key:github}", &env, &project).is_err());
+
+        let kept = Interpolation {
+            references: References::Keep,
+            ..trusted
+        };
+        let value = "${cmd:exit 1} ${TOKEN}";
+        assert_eq!(interpolate_with(value, &env, &kept).unwrap(), value);
+        assert!(interpolate_with("${MISSING}", &env, &kept).is_err());
+    }
+
+    #[test]
+    fn test_interpolate_json() {
+        let mut config = json!({ "mcp_servers": [{ "env": { "TOKEN": "${ENCHANT_TEST_UNSET}" } }] });
+        let interpolation = Interpolation {
+            keys: &|_| None,
+            trusted: true,
+            references: References::Resolve,
+        };
+        let err = interpolate_json(&mut config, &interpolation).unwrap_err();
+        assert!(err.to_string().ends_with("(at mcp_servers[0].env.TOKEN)"));
+    }
+}
@@ -0,0 +1,104 @@
+//! YAML front matter for markdown definition files (agents, commands).
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+
+use crate::error::Error;
+
+/// Splits a markdown file into its parsed front matter and body.
+///
+/// Files without a leading `---` block get the default front matter and the
+/// whole file as body.
+pub fn parse_front_matter<T: DeserializeOwned + Default>(content: &str) -> Result<(T, String), Error> {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return Ok((T::default(), content.to_string()));
+    };
+
+    let (yaml, body) = match rest.find("\n---") {
+        Some(end) => {
+            let body = &rest[end + "\n---".len()..];
+            let body = body.split_once('\n').map(|(_, b)| b).unwrap_or("");
+            (&rest[..end], body)
+        }
+        None => {
+            return Err(Error::SerdeError(
+                "Front matter is missing its closing `---`".to_string(),
+            ));
+        }
+    };
+
+    let front_matter = if yaml.trim().is_empty() {
+        T::default()
+    } else {
+        serde_yaml::from_str(yaml)?
+    };
+    Ok((front_matter, body.trim_start_matches(['\r', '\n']).to_string()))
+}
+
+/// Deserializes a list given either as a YAML sequence or a comma-separated string.
+pub fn string_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringList {
+        List(Vec<String>),
+        Joined(String),
+    }
+
+    Ok(Option::<StringList>::deserialize(deserializer)?.map(|list| match list {
+        StringList::List(items) => items,
+        StringList::Joined(s) => s
+            .split(',')
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect(),
+    }))
+}
+
+/// Reads every `*.md` file in a directory, sorted by path.
+/// A missing directory has no files.
+pub async fn read_markdown_dir(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut files = vec![];
+    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+        return files;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "md")
+            && let Ok(content) = tokio::fs::read_to_string(&path).await
+        {
+            files.push((path, content));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Deserialize)]
+    struct Meta {
+        name: Option<String>,
+        #[serde(default, deserialize_with = "string_list")]
+        tools: Option<Vec<String>>,
+    }
+
+    #[test]
+    fn test_front_matter() {
+        let (meta, body) =
+            parse_front_matter::<Meta>("---\nname: reviewer\ntools: Read, Grep\n---\n\nReview it.\n")
+                .unwrap();
+        assert_eq!(meta.name.as_deref(), Some("reviewer"));
+        assert_eq!(meta.tools.unwrap(), vec!["Read", "Grep"]);
+        assert_eq!(body, "Review it.\n");
+
+        let (meta, body) = parse_front_matter::<Meta>("Just a body").unwrap();
+        assert!(meta.name.is_none());
+        assert_eq!(body, "Just a body");
+    }
+}
@@ -0,0 +1,476 @@
+//! Checks JSON values against the subset of JSON Schema generated by
+//! `schemars`, reporting each problem with the path of the value.
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+/// A value that doesn't match the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    /// Path of the value, e.g. `mcp_servers[0].transport`. Empty for the root.
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.reason)
+        } else {
+            write!(f, "{}: {}", self.path, self.reason)
+        }
+    }
+}
+
+/// The problems of `value` according to `schema`.
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaError> {
+    validate_with(schema, value, true)
+}
+
+/// Like `validate`, but strings are only named in problems, for files that
+/// hold secrets such as `api-keys.json`.
+pub fn validate_secrets(schema: &Value, value: &Value) -> Vec<SchemaError> {
+    validate_with(schema, value, false)
+}
+
+fn validate_with(schema: &Value, value: &Value, show_strings: bool) -> Vec<SchemaError> {
+    let mut validator = Validator {
+        root: schema,
+        show_strings,
+        errors: vec![],
+    };
+    validator.check(schema, value, "");
+    validator.errors
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    /// Whether problems show the strings they found.
+    show_strings: bool,
+    errors: Vec<SchemaError>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, path: &str, reason: impl Into<String>) {
+        self.errors.push(SchemaError {
+            path: path.to_string(),
+            reason: reason.into(),
+        });
+    }
+
+    /// Resolves a local `$ref` such as `#/$defs/McpServerConfig`.
+    fn resolve(&self, schema: &'a Value) -> &'a Value {
+        match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.root.pointer(pointer))
+                .map(|schema| self.resolve(schema))
+                .unwrap_or(schema),
+            None => schema,
+        }
+    }
+
+    /// The problems of `value` against `schema` alone.
+    fn errors_of(&self, schema: &'a Value, value: &Value, path: &str) -> Vec<SchemaError> {
+        let mut validator = Validator {
+            root: self.root,
+            show_strings: self.show_strings,
+            errors: vec![],
+        };
+        validator.check(schema, value, path);
+        validator.errors
+    }
+
+    fn check(&mut self, schema: &'a Value, value: &Value, path: &str) {
+        let schema = self.resolve(schema);
+
+        if let Some(branches) = schema
+            .get("anyOf")
+            .or_else(|| schema.get("oneOf"))
+            .and_then(Value::as_array)
+        {
+            self.check_branches(branches, value, path);
+            return;
+        }
+
+        if let Some(expected) = schema.get("const")
+            && value != expected
+        {
+            let found = self.found(value);
+            self.error(path, format!("expected {expected}, found {found}"));
+            return;
+        }
+        if let Some(values) = schema.get("enum").and_then(Value::as_array)
+            && !values.contains(value)
+        {
+            self.error(
+                path,
+                format!(
+                    "expected {}, found {}",
+                    describe_values(values),
+                    self.found(value)
+                ),
+            );
+            return;
+        }
+        if let Some(types) = schema.get("type")
+            && !type_matches(types, value)
+        {
+            self.error(
+                path,
+                format!(
+                    "expected {}, found {}",
+                    describe_type(types),
+                    type_name(value)
+                ),
+            );
+            return;
+        }
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+            && value.as_f64().is_some_and(|n| n < minimum)
+        {
+            self.error(path, format!("must be at least {minimum}"));
+        }
+
+        match value {
+            Value::Object(object) => self.check_object(schema, object, path),
+            Value::Array(items) => {
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        self.check(item_schema, item, &format!("{path}[{i}]"));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `anyOf` and `oneOf`: the value must match one of the branches. When it
+    /// can only have meant one, such as the non-null branch of an optional
+    /// value, that branch's problems are reported.
+    fn check_branches(&mut self, branches: &'a [Value], value: &Value, path: &str) {
+        let mut candidates = vec![];
+        for branch in branches {
+            let errors = self.errors_of(branch, value, path);
+            if errors.is_empty() {
+                return;
+            }
+            if self.could_mean(branch, value) {
+                candidates.push(errors);
+            }
+        }
+        match <[_; 1]>::try_from(candidates) {
+            Ok([errors]) => self.errors.extend(errors),
+            Err(_) => {
+                // Tagged enums: the tag is what's wrong.
+                if let (Some((key, tags)), Value::Object(object)) = (self.tag(branches), value) {
+                    match object.get(key) {
+                        Some(tag) => self.error(
+                            &child_path(path, key),
+                            format!(
+                                "expected {}, found {}",
+                                describe_values(&tags),
+                                self.found(tag)
+                            ),
+                        ),
+                        None => self.error(path, format!("missing field `{key}`")),
+                    }
+                    return;
+                }
+                let expected = self.describe_branches(branches);
+                let found = self.found(value);
+                self.error(path, format!("expected {expected}, found {found}"));
+            }
+        }
+    }
+
+    /// The property whose constant value tells the branches apart, if any,
+    /// with its value in each branch.
+    fn tag(&self, branches: &'a [Value]) -> Option<(&'a str, Vec<Value>)> {
+        let properties = |branch| self.resolve(branch).get("properties")?.as_object();
+        let first = properties(branches.first()?)?;
+        first.keys().find_map(|key| {
+            let tags = branches
+                .iter()
+                .map(|branch| {
+                    let property = properties(branch)?.get(key)?;
+                    self.resolve(property).get("const").cloned()
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some((key.as_str(), tags))
+        })
+    }
+
+    /// Whether `value` has the type of `branch`, and the values of its
+    /// constant properties, like the tag of an enum.
+    fn could_mean(&self, branch: &'a Value, value: &Value) -> bool {
+        let branch = self.resolve(branch);
+        if branch.get("const").is_some() || branch.get("enum").is_some() {
+            return false;
+        }
+        if let Some(types) = branch.get("type")
+            && !type_matches(types, value)
+        {
+            return false;
+        }
+        let properties = branch.get("properties").and_then(Value::as_object);
+        properties.into_iter().flatten().all(|(key, property)| {
+            match (self.resolve(property).get("const"), value.get(key)) {
+                (Some(expected), Some(actual)) => expected == actual,
+                _ => true,
+            }
+        })
+    }
+
+    fn check_object(&mut self, schema: &'a Value, object: &Map<String, Value>, path: &str) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let child = |key: &str| child_path(path, key);
+
+        for key in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(key) {
+                self.error(path, format!("missing field `{key}`"));
+            }
+        }
+
+        for (key, item) in object {
+            match properties.and_then(|p| p.get(key)) {
+                Some(property) => self.check(property, item, &child(key)),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        let known = properties.into_iter().flatten().map(|(k, _)| k.as_str());
+                        let reason = match closest(key, known) {
+                            Some(name) => format!("unknown field, did you mean `{name}`?"),
+                            None => "unknown field".to_string(),
+                        };
+                        self.error(&child(key), reason);
+                    }
+                    Some(additional @ Value::Object(_)) => {
+                        self.check(additional, item, &child(key))
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// A value as shown in problems. Objects and arrays are only named, since
+    /// they may hold secrets, and so are strings unless `show_strings` is set.
+    fn found(&self, value: &Value) -> String {
+        match value {
+            Value::Object(_) | Value::Array(_) => type_name(value).to_string(),
+            Value::String(_) if !self.show_strings => type_name(value).to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// A short description of the values `schema` accepts.
+    fn describe(&self, schema: &'a Value) -> String {
+        let schema = self.resolve(schema);
+        if let Some(expected) = schema.get("const") {
+            return expected.to_string();
+        }
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            return describe_values(values);
+        }
+        if let Some(branches) = schema
+            .get("anyOf")
+            .or_else(|| schema.get("oneOf"))
+            .and_then(Value::as_array)
+        {
+            return self.describe_branches(branches);
+        }
+        match schema.get("type") {
+            Some(types) => describe_type(types),
+            None => "any value".to_string(),
+        }
+    }
+
+    /// Branches that are all constants, like a documented enum, are described
+    /// as a single list of values.
+    fn describe_branches(&self, branches: &'a [Value]) -> String {
+        let constants: Option<Vec<Value>> = branches
+            .iter()
+            .map(|branch| {
+                let branch = self.resolve(branch);
+                match (branch.get("const"), branch.get("enum")) {
+                    (Some(value), _) => Some(vec![value.clone()]),
+                    (None, Some(Value::Array(values))) => Some(values.clone()),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.concat());
+        match constants {
+            Some(values) => describe_values(&values),
+            None => branches
+                .iter()
+                .map(|branch| self.describe(branch))
+                .collect::<Vec<_>>()
+                .join(" or "),
+        }
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn type_matches(types: &Value, value: &Value) -> bool {
+    let matches = |name: &str| match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    };
+    match types {
+        Value::String(name) => matches(name),
+        Value::Array(names) => names.iter().filter_map(Value::as_str).any(matches),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn describe_type(types: &Value) -> String {
+    match types {
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        other => other.as_str().unwrap_or("any value").to_string(),
+    }
+}
+
+fn describe_values(values: &[Value]) -> String {
+    match values {
+        [value] => value.to_string(),
+        _ => {
+            let values = values.iter().map(Value::to_string).collect::<Vec<_>>();
+            format!("one of {}", values.join(", "))
+        }
+    }
+}
+
+/// The known name closest to a misspelt `name`, if any is close enough.
+fn closest<'a>(name: &str, known: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    known
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= 2.max(candidate.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "$defs": {
+                "Server": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {
+                        "name": { "type": "string" },
+                        "transport": { "enum": ["stdio", "sse"] },
+                        "timeout": { "type": ["integer", "null"], "minimum": 0 },
+                        "env": { "type": "object", "additionalProperties": { "type": "string" } }
+                    },
+                    "required": ["name"]
+                }
+            },
+            "type": "object",
+            "properties": {
+                "servers": { "type": "array", "items": { "$ref": "#/$defs/Server" } },
+                "server": { "anyOf": [{ "$ref": "#/$defs/Server" }, { "type": "null" }] },
+                "key": { "oneOf": [
+                    { "type": "object", "properties": { "provider": { "const": "openai" } } },
+                    { "type": "object", "properties": { "provider": { "const": "ollama" } } }
+                ] }
+            }
+        });
+        let errors = |value| {
+            validate(&schema, &value)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(errors(json!({ "servers": [{ "name": "a", "timeout": 5 }] })).is_empty());
+        assert_eq!(
+            errors(json!({
+                "servers": [
+                    { "name": "a", "transport": "http", "timout": 5 },
+                    { "timeout": -1, "env": { "TOKEN": 1 } }
+                ],
+                "server": { "name": 1 }
+            })),
+            [
+                "server.name: expected string, found integer",
+                "servers[0].timout: unknown field, did you mean `timeout`?",
+                r#"servers[0].transport: expected one of "stdio", "sse", found "http""#,
+                "servers[1]: missing field `name`",
+                "servers[1].env.TOKEN: expected string, found integer",
+                "servers[1].timeout: must be at least 0",
+            ]
+        );
+        assert_eq!(
+            errors(json!({ "server": "a" })),
+            [r#"server: expected object or null, found "a""#]
+        );
+        assert_eq!(
+            errors(json!({ "key": { "provider": "olama", "api_key": "secret" } })),
+            [r#"key.provider: expected one of "openai", "ollama", found "olama""#]
+        );
+
+        let value = json!({ "key": "sk-secret" });
+        let errors = validate_secrets(&schema, &value);
+        assert_eq!(errors.len(), 1);
+        assert!(!errors[0].to_string().contains("sk-secret"));
+        assert!(errors[0].to_string().ends_with("found string"));
+    }
+}
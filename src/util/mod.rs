@@ -2,6 +2,10 @@ use std::path::{Path, PathBuf};
 
 use crate::agent::tools::tool_error::ToolError;
 
+pub mod env;
+pub mod front_matter;
+pub mod json_schema;
+
 pub fn format_path(path: impl AsRef<Path>) -> PathBuf {
     let path = path.as_ref();
     let working_dir = if let Ok(working_dir) = std::env::current_dir() {
@@ -13,10 +17,10 @@ pub fn format_path(path: impl AsRef<Path>) -> PathBuf {
     if path.starts_with(&working_dir) {
         return path
             .strip_prefix(&working_dir)
-            .unwrap_or(&path)
+            .unwrap_or(path)
             .to_path_buf();
     }
-    return path.to_path_buf();
+    path.to_path_buf()
 }
 
 pub fn assert_working_directory(path: impl AsRef<Path>) -> Result<(), ToolError> {